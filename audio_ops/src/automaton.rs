@@ -0,0 +1,303 @@
+//! # Elementary cellular automaton rhythms
+//!
+//! `ca:<RULE>:<WIDTH>[:<ROW>]` keeps a row of WIDTH cells per channel, reads
+//! it as a dense gate pattern against `0..1` phase, and advances one
+//! generation with Wolfram rule RULE on each forward cycle wrap. The row wraps
+//! around at its edges.
+use audio_vm::{CHANNELS, Frame, Op, Sample, Stack};
+use rand::{RngExt, SeedableRng, rngs::SmallRng};
+
+const MAX_WIDTH: usize = 128;
+
+/// Where the start row came from, so migration can tell a new start row from
+/// one only derived from a changed width.
+#[derive(Clone, Copy, PartialEq)]
+enum Start {
+    Row(u128),
+    Seeded(u64),
+    Centre,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+struct Spec {
+    rule: u8,
+    width: usize,
+    start: Start,
+    initial: u128,
+}
+
+impl Spec {
+    fn parse(spec: &str, seed: Option<u64>) -> Option<Self> {
+        let mut args = spec.split(':');
+        let rule = args.next()?.parse::<u8>().ok()?;
+        let width = args.next()?.parse::<usize>().ok()?;
+        if !(1..=MAX_WIDTH).contains(&width) {
+            return None;
+        }
+        let (start, initial) = match args.next() {
+            Some(row) => {
+                let row = parse_row(row, width)?;
+                (Start::Row(row), row)
+            }
+            None => match seed {
+                Some(seed) => (
+                    Start::Seeded(seed),
+                    SmallRng::seed_from_u64(seed).random::<u128>() & mask(width),
+                ),
+                None => (Start::Centre, 1 << (width / 2)),
+            },
+        };
+        args.next().is_none().then_some(Spec {
+            rule,
+            width,
+            start,
+            initial,
+        })
+    }
+
+    /// Same start row up to width: explicit rows compare over the cells both
+    /// widths share, while derived rows only change with the seed.
+    fn same_start(&self, other: &Spec) -> bool {
+        match (self.start, other.start) {
+            (Start::Row(row), Start::Row(other_row)) => {
+                row & mask(other.width) == other_row & mask(self.width)
+            }
+            (start, other_start) => start == other_start,
+        }
+    }
+
+    fn step(&self, row: u128) -> u128 {
+        let width = self.width;
+        let mut next = 0;
+        for cell in 0..width {
+            let left = row >> ((cell + width - 1) % width) & 1;
+            let centre = row >> cell & 1;
+            let right = row >> ((cell + 1) % width) & 1;
+            let neighbourhood = (left << 2) | (centre << 1) | right;
+            next |= u128::from(self.rule >> neighbourhood & 1) << cell;
+        }
+        next
+    }
+}
+
+fn mask(width: usize) -> u128 {
+    if width == MAX_WIDTH {
+        u128::MAX
+    } else {
+        (1 << width) - 1
+    }
+}
+
+/// Dense `x`/`.` row, left-aligned; shorter rows are padded with empty cells.
+fn parse_row(row: &str, width: usize) -> Option<u128> {
+    if row.is_empty() || row.chars().count() > width {
+        return None;
+    }
+    row.chars()
+        .enumerate()
+        .try_fold(0, |bits, (cell, ch)| match ch {
+            'x' | 'X' => Some(bits | 1 << cell),
+            '.' => Some(bits),
+            _ => None,
+        })
+}
+
+pub struct CellularAutomaton {
+    spec: Option<Spec>,
+    trigger: bool,
+    rows: [u128; CHANNELS],
+    previous_phases: [Option<Sample>; CHANNELS],
+    previous_indices: [Option<usize>; CHANNELS],
+}
+
+impl CellularAutomaton {
+    /// `ca:` — gate high over live cells.
+    pub fn gate(spec: &str, seed: Option<u64>) -> Self {
+        Self::with_mode(spec, seed, false)
+    }
+
+    /// `catrig:` — one-sample trigger on entering a live cell.
+    pub fn trigger(spec: &str, seed: Option<u64>) -> Self {
+        Self::with_mode(spec, seed, true)
+    }
+
+    fn with_mode(spec: &str, seed: Option<u64>, trigger: bool) -> Self {
+        let parsed = Spec::parse(spec, seed);
+        if parsed.is_none() {
+            log::warn!("Invalid cellular automaton: {}", spec);
+        }
+        Self {
+            rows: [parsed.map_or(0, |spec| spec.initial); CHANNELS],
+            spec: parsed,
+            trigger,
+            previous_phases: [None; CHANNELS],
+            previous_indices: [None; CHANNELS],
+        }
+    }
+}
+
+impl Op for CellularAutomaton {
+    fn perform(&mut self, stack: &mut Stack) {
+        let phase = stack.pop();
+        let mut output: Frame = [0.0; CHANNELS];
+        let Some(spec) = self.spec else {
+            stack.push(&output);
+            return;
+        };
+        for (channel, (out, &phase)) in output.iter_mut().zip(&phase).enumerate() {
+            let phase = if phase.is_finite() {
+                phase.rem_euclid(1.0)
+            } else {
+                0.0
+            };
+            let forward_cycle_wrap = self.previous_phases[channel].is_some_and(|prev| prev > phase);
+            if forward_cycle_wrap {
+                self.rows[channel] = spec.step(self.rows[channel]);
+            }
+            let index = ((phase * spec.width as Sample) as usize).min(spec.width - 1);
+            let alive = self.rows[channel] >> index & 1 == 1;
+            let entered = self.previous_indices[channel] != Some(index) || forward_cycle_wrap;
+            *out = if alive && (!self.trigger || entered) {
+                1.0
+            } else {
+                0.0
+            };
+            self.previous_phases[channel] = Some(phase);
+            self.previous_indices[channel] = Some(index);
+        }
+        stack.push(&output);
+    }
+
+    /// Rule and width edits keep evolving the current generation; editing the
+    /// initial row (or the `seed:` behind it) restarts from the new row.
+    fn migrate(&mut self, other: &mut dyn Op) {
+        if let Some(other) = other.downcast_mut::<Self>()
+            && let (Some(spec), Some(other_spec)) = (self.spec, other.spec)
+            && spec.same_start(&other_spec)
+        {
+            self.previous_phases = other.previous_phases;
+            for (row, &other_row) in self.rows.iter_mut().zip(&other.rows) {
+                *row = other_row & mask(spec.width);
+            }
+            if spec.width == other_spec.width {
+                self.previous_indices = other.previous_indices;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn perform(op: &mut CellularAutomaton, phase: Sample) -> Sample {
+        let mut stack = Stack::new();
+        stack.push(&[phase; CHANNELS]);
+        op.perform(&mut stack);
+        stack.pop()[0]
+    }
+
+    fn read_row(op: &mut CellularAutomaton, width: usize) -> String {
+        (0..width)
+            .map(|cell| {
+                if perform(op, (cell as Sample + 0.5) / width as Sample) > 0.0 {
+                    'x'
+                } else {
+                    '.'
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rule_90_grows_a_sierpinski_triangle_one_generation_per_wrap() {
+        let mut ca = CellularAutomaton::gate("90:7", None);
+        assert_eq!(read_row(&mut ca, 7), "...x...");
+        assert_eq!(read_row(&mut ca, 7), "..x.x..");
+        assert_eq!(read_row(&mut ca, 7), ".x...x.");
+        assert_eq!(read_row(&mut ca, 7), "x.x.x.x");
+    }
+
+    #[test]
+    fn explicit_row_wraps_around_edges() {
+        let mut ca = CellularAutomaton::gate("30:4:x", None);
+        assert_eq!(read_row(&mut ca, 4), "x...");
+        assert_eq!(read_row(&mut ca, 4), "xx.x");
+    }
+
+    #[test]
+    fn trigger_fires_on_entering_live_cells() {
+        let mut ca = CellularAutomaton::trigger("0:2:xx", None);
+        assert_eq!(perform(&mut ca, 0.0), 1.0);
+        assert_eq!(perform(&mut ca, 0.25), 0.0);
+        assert_eq!(perform(&mut ca, 0.5), 1.0);
+        // Rule 0 clears the row on the first wrap.
+        assert_eq!(perform(&mut ca, 0.0), 0.0);
+    }
+
+    #[test]
+    fn seed_picks_a_reproducible_random_row() {
+        let row = |seed| read_row(&mut CellularAutomaton::gate("110:32", Some(seed)), 32);
+        assert_eq!(row(1), row(1));
+        assert_ne!(row(1), row(2));
+        assert_ne!(row(1), "................x...............");
+    }
+
+    #[test]
+    fn migration_keeps_generation_unless_initial_row_changes() {
+        let mut previous = CellularAutomaton::gate("90:7", None);
+        read_row(&mut previous, 7);
+        read_row(&mut previous, 7);
+
+        let mut next = CellularAutomaton::gate("90:7", None);
+        next.migrate(&mut previous);
+        perform(&mut next, 0.0);
+        assert_eq!(read_row(&mut next, 7), ".x...x.");
+
+        let mut reseeded = CellularAutomaton::gate("90:7:x", None);
+        reseeded.migrate(&mut next);
+        assert_eq!(read_row(&mut reseeded, 7), "x......");
+    }
+
+    #[test]
+    fn width_edits_keep_evolving_derived_start_rows() {
+        for seed in [None, Some(7)] {
+            let mut previous = CellularAutomaton::gate("90:7", seed);
+            read_row(&mut previous, 7);
+            read_row(&mut previous, 7);
+
+            let mut wider = CellularAutomaton::gate("90:9", seed);
+            wider.migrate(&mut previous);
+            assert_eq!(wider.rows, previous.rows);
+        }
+
+        let mut previous = CellularAutomaton::gate("90:7", None);
+        read_row(&mut previous, 7);
+        assert_eq!(read_row(&mut previous, 7), "..x.x..");
+        let mut wider = CellularAutomaton::gate("90:9", None);
+        wider.migrate(&mut previous);
+        // The next generation of the running row, not of a fresh centre cell.
+        assert_eq!(read_row(&mut wider, 9), ".x...x...");
+
+        let mut reseeded = CellularAutomaton::gate("90:7", Some(2));
+        reseeded.migrate(&mut CellularAutomaton::gate("90:7", Some(1)));
+        assert_eq!(reseeded.rows[0], reseeded.spec.unwrap().initial);
+    }
+
+    #[test]
+    fn invalid_specs_output_zero() {
+        for spec in [
+            "",
+            "30",
+            "256:8",
+            "30:0",
+            "30:129",
+            "30:4:xxxxx",
+            "30:4:x1",
+            "30:4:x:y",
+        ] {
+            let mut ca = CellularAutomaton::gate(spec, None);
+            assert_eq!(perform(&mut ca, 0.5), 0.0, "{spec}");
+        }
+    }
+}
@@ -1,3 +1,4 @@
+mod automaton;
 mod biquad;
 mod buffer;
 mod channel;
@@ -12,8 +13,10 @@ mod function;
 mod input;
 mod lag;
 mod limit;
+mod markov;
 mod metro;
 mod midi;
+mod midi_out;
 mod noise;
 mod noop;
 mod normalise;
@@ -30,16 +33,18 @@ mod reverb;
 mod sample_and_hold;
 mod sampler;
 mod scale;
+mod smf;
 mod spectral_transform;
 mod stack;
+mod transport;
 mod variable;
 mod wah;
 mod yin;
 
 pub use self::{
-    biquad::*, channel::*, constant::*, convolution::*, crush::*, delay::*, envelopes::*,
-    feedback::*, filters::*, function::*, input::*, lag::*, limit::*, metro::*, midi::*, noise::*,
-    noop::*, normalise::*, osc::*, pan::*, param::*, pattern::*, phasor::*, poly::*, pulse::*,
-    random::*, reverb::*, sample_and_hold::*, sampler::*, scale::*, spectral_transform::*,
-    stack::*, variable::*, wah::*, yin::*,
+    automaton::*, biquad::*, channel::*, constant::*, convolution::*, crush::*, delay::*,
+    envelopes::*, feedback::*, filters::*, function::*, input::*, lag::*, limit::*, markov::*,
+    metro::*, midi::*, midi_out::*, noise::*, noop::*, normalise::*, osc::*, pan::*, param::*,
+    pattern::*, phasor::*, poly::*, pulse::*, random::*, reverb::*, sample_and_hold::*, sampler::*,
+    scale::*, smf::*, spectral_transform::*, stack::*, transport::*, variable::*, wah::*, yin::*,
 };
@@ -0,0 +1,206 @@
+use crate::pattern::parse_value_constant;
+use audio_vm::{CHANNELS, Frame, Op, Sample, Stack};
+use itertools::izip;
+use rand::{RngExt, SeedableRng, rngs::SmallRng};
+
+/// Weighted transition table over state values, parsed from
+/// `FROM>NEXT*W,NEXT;FROM>...`. The first listed state is the initial one.
+struct Chain {
+    values: Vec<Sample>,
+    /// Per state: next state indices with cumulative weights. Empty for
+    /// states that are only ever reached (dead ends).
+    transitions: Vec<Vec<(usize, Sample)>>,
+}
+
+impl Chain {
+    fn parse(table: &str) -> Option<Self> {
+        let mut chain = Chain {
+            values: Vec::new(),
+            transitions: Vec::new(),
+        };
+        let mut rows = Vec::new();
+        for row in table.split(';') {
+            let (from, next) = row.split_once('>')?;
+            let from = parse_value_constant(from).ok()?;
+            if chain.values.contains(&from) {
+                return None;
+            }
+            chain.values.push(from);
+            chain.transitions.push(Vec::new());
+            rows.push(next);
+        }
+        for (state, next) in rows.into_iter().enumerate() {
+            let mut total = 0.0;
+            for target in next.split(',') {
+                let (value, weight) = match target.split_once('*') {
+                    Some((value, weight)) => (value, weight.parse::<Sample>().ok()?),
+                    None => (target, 1.0),
+                };
+                if !(weight.is_finite() && weight > 0.0) {
+                    return None;
+                }
+                let value = parse_value_constant(value).ok()?;
+                let index = chain.index(value).unwrap_or_else(|| {
+                    chain.values.push(value);
+                    chain.transitions.push(Vec::new());
+                    chain.values.len() - 1
+                });
+                total += weight;
+                chain.transitions[state].push((index, total));
+            }
+        }
+        Some(chain)
+    }
+
+    fn index(&self, value: Sample) -> Option<usize> {
+        self.values.iter().position(|&x| x == value)
+    }
+
+    /// Pick the next state; dead ends restart from the initial state.
+    fn step(&self, state: usize, rng: &mut SmallRng) -> usize {
+        let transitions = &self.transitions[state];
+        let Some(&(_, total)) = transitions.last() else {
+            return 0;
+        };
+        let x = rng.random_range(0.0..total);
+        transitions
+            .iter()
+            .find(|&&(_, weight)| x < weight)
+            .map_or(transitions[transitions.len() - 1].0, |&(index, _)| index)
+    }
+}
+
+/// Markov-chain sequencer: steps a weighted transition table on each rising
+/// edge of the trigger, per channel, and holds the current state value.
+pub struct Markov {
+    chain: Option<Chain>,
+    rng: SmallRng,
+    states: [usize; CHANNELS],
+    previous_trigger: Frame,
+}
+
+impl Markov {
+    pub fn new(table: &str) -> Self {
+        Self::with_seed(table, None)
+    }
+
+    pub fn with_seed(table: &str, seed: Option<u64>) -> Self {
+        let chain = Chain::parse(table);
+        if chain.is_none() {
+            log::warn!("Invalid markov table: {}", table);
+        }
+        Self {
+            chain,
+            rng: seed.map_or_else(rand::make_rng, SmallRng::seed_from_u64),
+            states: [0; CHANNELS],
+            previous_trigger: [0.0; CHANNELS],
+        }
+    }
+}
+
+impl Op for Markov {
+    fn perform(&mut self, stack: &mut Stack) {
+        let trigger = stack.pop();
+        let Some(chain) = &self.chain else {
+            stack.push(&[0.0; CHANNELS]);
+            return;
+        };
+        let mut output = [0.0; CHANNELS];
+        for (out, state, previous, &trig) in izip!(
+            &mut output,
+            &mut self.states,
+            &mut self.previous_trigger,
+            &trigger
+        ) {
+            if *previous <= 0.0 && trig > 0.0 {
+                *state = chain.step(*state, &mut self.rng);
+            }
+            *previous = trig;
+            *out = chain.values[*state];
+        }
+        stack.push(&output);
+    }
+
+    /// Keep the current state by value, so editing weights or adding states
+    /// continues from the same note; states missing from the new table
+    /// restart from its initial state.
+    fn migrate(&mut self, other: &mut dyn Op) {
+        if let Some(other) = other.downcast_mut::<Self>() {
+            if let (Some(chain), Some(other_chain)) = (&self.chain, &other.chain) {
+                for (state, &other_state) in self.states.iter_mut().zip(&other.states) {
+                    *state = chain.index(other_chain.values[other_state]).unwrap_or(0);
+                }
+            }
+            self.previous_trigger = other.previous_trigger;
+            self.rng = other.rng.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(op: &mut Markov, triggers: &[Sample]) -> Vec<Sample> {
+        let mut stack = Stack::new();
+        triggers
+            .iter()
+            .map(|&trig| {
+                stack.push(&[trig; CHANNELS]);
+                op.perform(&mut stack);
+                stack.pop()[0]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn steps_on_rising_edges_and_follows_the_table() {
+        let mut markov = Markov::with_seed("60>64;64>67;67>60", Some(1));
+        assert_eq!(
+            run(&mut markov, &[0.0, 1.0, 1.0, 0.0, 1.0, 0.0, 1.0]),
+            vec![60.0, 64.0, 64.0, 64.0, 67.0, 67.0, 60.0]
+        );
+    }
+
+    #[test]
+    fn weights_bias_choices_and_seed_reproduces_them() {
+        let table = "C4>E4*9,G4;E4>C4;G4>C4";
+        let triggers = [0.0, 1.0].repeat(400);
+        let a = run(&mut Markov::with_seed(table, Some(7)), &triggers);
+        let b = run(&mut Markov::with_seed(table, Some(7)), &triggers);
+        assert_eq!(a, b);
+        // Every other step leaves C4; count where it went.
+        let e = a
+            .windows(2)
+            .filter(|w| w[0] == 60.0 && w[1] == 64.0)
+            .count();
+        let g = a
+            .windows(2)
+            .filter(|w| w[0] == 60.0 && w[1] == 67.0)
+            .count();
+        assert!(e > 4 * g, "{e} vs {g}");
+    }
+
+    #[test]
+    fn dead_ends_restart_from_the_initial_state() {
+        let mut markov = Markov::with_seed("60>72", None);
+        assert_eq!(run(&mut markov, &[1.0, 0.0, 1.0]), vec![72.0, 72.0, 60.0]);
+    }
+
+    #[test]
+    fn migration_keeps_current_state_by_value() {
+        let mut old = Markov::with_seed("60>64;64>60", Some(1));
+        run(&mut old, &[1.0]);
+        let mut new = Markov::with_seed("67>60;60>64*2,67;64>67", Some(1));
+        new.migrate(&mut old);
+        assert_eq!(run(&mut new, &[1.0, 0.0, 1.0]), vec![64.0, 64.0, 67.0]);
+    }
+
+    #[test]
+    fn invalid_tables_output_zero() {
+        for table in ["", "60", "60>", "60>64*0", "60>x", "60>64;60>67", "60>64*"] {
+            let mut markov = Markov::new(table);
+            assert_eq!(run(&mut markov, &[1.0]), vec![0.0], "{table}");
+        }
+    }
+}
@@ -1,11 +1,30 @@
 use audio_vm::{CHANNELS, Frame, Op, Sample, Stack, Statement, migrate_program_state};
 use std::sync::{
     Arc,
-    atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering},
+    atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering},
 };
 
 pub const MAX_MIDI_EVENTS_PER_FRAME: usize = 64;
 pub const MIDI_EVENT_RING_CAPACITY: usize = 1024;
+pub const MIDI_CHANNELS: usize = 16;
+pub const SUSTAIN_PEDAL: u8 = 64;
+pub const MOD_WHEEL: u8 = 1;
+/// MPE timbre dimension, sent per note channel.
+pub const TIMBRE: u8 = 74;
+const RPN_MSB: u8 = 101;
+const RPN_LSB: u8 = 100;
+const DATA_ENTRY: u8 = 6;
+/// RPN 6, the MPE Configuration Message.
+const MPE_CONFIGURATION: u32 = 6;
+const NULL_RPN: u32 = 0x3fff;
+/// MIDI clock resolution, pulses per quarter note.
+pub const MIDI_CLOCK_PPQN: u32 = 24;
+/// Weight of each measured clock pulse interval in the tempo estimate.
+const CLOCK_SMOOTHING: f64 = 0.1;
+/// Pulses missing before the clock counts as stopped.
+const CLOCK_TIMEOUT_PULSES: f64 = 4.0;
+/// Controller slot updated by every channel, read by omni `cc`/`bend`.
+const OMNI: usize = MIDI_CHANNELS;
 
 const SILENCE: Frame = [0.0; CHANNELS];
 
@@ -13,14 +32,35 @@ const SILENCE: Frame = [0.0; CHANNELS];
 pub enum MidiEventKind {
     NoteOn,
     NoteOff,
+    ControlChange,
+    PitchBend,
+    ChannelPressure,
+    /// System real-time messages, channel-less.
+    Clock,
+    Start,
+    Continue,
+    Stop,
 }
 
+/// Decoded MIDI message. For `ControlChange`, `note` is the controller number
+/// and `velocity` the normalized value `0..1`; for `PitchBend`, `velocity` is
+/// the bend in `-1..1`; for `ChannelPressure`, `velocity` is the pressure
+/// `0..1`.
+///
+/// `timestamp` is the arrival time in microseconds on the server's MIDI
+/// clock, used to place the event on an exact frame; 0 means "as soon as
+/// possible". Frame slices delivered to ops no longer carry it.
+///
+/// `source` tags which of the server's MIDI inputs sent the event, so two
+/// keyboards on the same channel don't release each other's notes.
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct MidiEvent {
     pub kind: MidiEventKind,
     pub channel: u8,
     pub note: u8,
     pub velocity: Sample,
+    pub timestamp: u64,
+    pub source: u8,
 }
 
 impl MidiEvent {
@@ -30,6 +70,8 @@ impl MidiEvent {
             channel: channel.min(15),
             note: note.min(127),
             velocity: velocity.clamp(0.0, 1.0),
+            timestamp: 0,
+            source: 0,
         }
     }
 
@@ -39,11 +81,143 @@ impl MidiEvent {
             channel: channel.min(15),
             note: note.min(127),
             velocity: 0.0,
+            timestamp: 0,
+            source: 0,
+        }
+    }
+
+    pub fn control_change(channel: u8, controller: u8, value: Sample) -> Self {
+        MidiEvent {
+            kind: MidiEventKind::ControlChange,
+            channel: channel.min(15),
+            note: controller.min(127),
+            velocity: value.clamp(0.0, 1.0),
+            timestamp: 0,
+            source: 0,
+        }
+    }
+
+    pub fn pitch_bend(channel: u8, bend: Sample) -> Self {
+        MidiEvent {
+            kind: MidiEventKind::PitchBend,
+            channel: channel.min(15),
+            note: 0,
+            velocity: bend.clamp(-1.0, 1.0),
+            timestamp: 0,
+            source: 0,
+        }
+    }
+
+    pub fn channel_pressure(channel: u8, pressure: Sample) -> Self {
+        MidiEvent {
+            kind: MidiEventKind::ChannelPressure,
+            channel: channel.min(15),
+            note: 0,
+            velocity: pressure.clamp(0.0, 1.0),
+            timestamp: 0,
+            source: 0,
+        }
+    }
+
+    /// Clock, start, continue or stop.
+    pub fn realtime(kind: MidiEventKind) -> Self {
+        MidiEvent {
+            kind,
+            channel: 0,
+            note: 0,
+            velocity: 0.0,
+            timestamp: 0,
+            source: 0,
+        }
+    }
+
+    /// The same event stamped with its arrival time.
+    pub fn at(self, timestamp: u64) -> Self {
+        MidiEvent { timestamp, ..self }
+    }
+
+    /// The same event tagged with the input it came from.
+    pub fn with_source(self, source: u8) -> Self {
+        MidiEvent { source, ..self }
+    }
+}
+
+/// Decode a raw channel voice or real-time message; others are ignored.
+pub fn decode_message(message: &[u8]) -> Option<MidiEvent> {
+    let status = *message.first()?;
+    let realtime = match status {
+        0xf8 => Some(MidiEventKind::Clock),
+        0xfa => Some(MidiEventKind::Start),
+        0xfb => Some(MidiEventKind::Continue),
+        0xfc => Some(MidiEventKind::Stop),
+        _ => None,
+    };
+    if let Some(kind) = realtime {
+        return Some(MidiEvent::realtime(kind));
+    }
+    let channel = status & 0x0f;
+    match status & 0xf0 {
+        0x80 if message.len() >= 3 => Some(MidiEvent::note_off(channel, message[1])),
+        0x90 if message.len() >= 3 => {
+            let velocity = f64::from(message[2]) / 127.0;
+            if velocity > 0.0 {
+                Some(MidiEvent::note_on(channel, message[1], velocity))
+            } else {
+                Some(MidiEvent::note_off(channel, message[1]))
+            }
         }
+        0xb0 if message.len() >= 3 => Some(MidiEvent::control_change(
+            channel,
+            message[1],
+            f64::from(message[2]) / 127.0,
+        )),
+        0xd0 if message.len() >= 2 => Some(MidiEvent::channel_pressure(
+            channel,
+            f64::from(message[1]) / 127.0,
+        )),
+        0xe0 if message.len() >= 3 => {
+            let value = (i32::from(message[2]) << 7 | i32::from(message[1])) - 8192;
+            Some(MidiEvent::pitch_bend(channel, f64::from(value) / 8192.0))
+        }
+        _ => None,
     }
 }
 
-struct AtomicMidiEvent {
+/// The inverse of `decode_message`: the message bytes and how many of them
+/// are used.
+pub fn encode_message(event: &MidiEvent) -> ([u8; 3], usize) {
+    let channel = event.channel & 0x0f;
+    let data = |value: f64| (value.clamp(0.0, 1.0) * 127.0).round() as u8;
+    match event.kind {
+        MidiEventKind::NoteOn => (
+            [
+                0x90 | channel,
+                event.note & 0x7f,
+                data(event.velocity).max(1),
+            ],
+            3,
+        ),
+        MidiEventKind::NoteOff => ([0x80 | channel, event.note & 0x7f, 0], 3),
+        MidiEventKind::ControlChange => {
+            ([0xb0 | channel, event.note & 0x7f, data(event.velocity)], 3)
+        }
+        MidiEventKind::ChannelPressure => ([0xd0 | channel, data(event.velocity), 0], 2),
+        MidiEventKind::PitchBend => {
+            let value =
+                ((event.velocity.clamp(-1.0, 1.0) * 8192.0).round() as i32 + 8192).clamp(0, 16383);
+            (
+                [0xe0 | channel, (value & 0x7f) as u8, (value >> 7) as u8],
+                3,
+            )
+        }
+        MidiEventKind::Clock => ([0xf8, 0, 0], 1),
+        MidiEventKind::Start => ([0xfa, 0, 0], 1),
+        MidiEventKind::Continue => ([0xfb, 0, 0], 1),
+        MidiEventKind::Stop => ([0xfc, 0, 0], 1),
+    }
+}
+
+pub(crate) struct AtomicMidiEvent {
     meta: AtomicU32,
     velocity: AtomicU64,
 }
@@ -58,21 +232,38 @@ impl Default for AtomicMidiEvent {
 }
 
 impl AtomicMidiEvent {
-    fn store(&self, event: MidiEvent) {
+    pub(crate) fn store(&self, event: MidiEvent) {
         self.velocity
-            .store(event.velocity.clamp(0.0, 1.0).to_bits(), Ordering::Relaxed);
+            .store(event.velocity.clamp(-1.0, 1.0).to_bits(), Ordering::Relaxed);
         let kind = match event.kind {
             MidiEventKind::NoteOn => 1u32,
             MidiEventKind::NoteOff => 2u32,
+            MidiEventKind::ControlChange => 3u32,
+            MidiEventKind::PitchBend => 4u32,
+            MidiEventKind::ChannelPressure => 5u32,
+            MidiEventKind::Clock => 6u32,
+            MidiEventKind::Start => 7u32,
+            MidiEventKind::Continue => 8u32,
+            MidiEventKind::Stop => 9u32,
         };
-        let meta = kind | ((event.channel as u32) << 8) | ((event.note as u32) << 16);
+        let meta = kind
+            | ((event.channel as u32) << 8)
+            | ((event.note as u32) << 16)
+            | ((event.source as u32) << 24);
         self.meta.store(meta, Ordering::Release);
     }
 
-    fn load(&self) -> MidiEvent {
+    pub(crate) fn load(&self) -> MidiEvent {
         let meta = self.meta.load(Ordering::Acquire);
         let kind = match meta & 0xff {
             1 => MidiEventKind::NoteOn,
+            3 => MidiEventKind::ControlChange,
+            4 => MidiEventKind::PitchBend,
+            5 => MidiEventKind::ChannelPressure,
+            6 => MidiEventKind::Clock,
+            7 => MidiEventKind::Start,
+            8 => MidiEventKind::Continue,
+            9 => MidiEventKind::Stop,
             _ => MidiEventKind::NoteOff,
         };
         MidiEvent {
@@ -80,6 +271,8 @@ impl AtomicMidiEvent {
             channel: ((meta >> 8) & 0xff) as u8,
             note: ((meta >> 16) & 0xff) as u8,
             velocity: Sample::from_bits(self.velocity.load(Ordering::Relaxed)),
+            timestamp: 0,
+            source: (meta >> 24) as u8,
         }
     }
 }
@@ -89,9 +282,43 @@ impl AtomicMidiEvent {
 /// The audio callback writes the current frame's drained MIDI events before
 /// running the VM. `mpoly` ops read the same non-consuming slice, so multiple
 /// `mpoly` instances can respond to the same keyboard events.
+///
+/// It also latches the latest control change, pitch bend and channel
+/// pressure values per channel (plus an omni slot), so `cc:`/`bend` read the
+/// current controller position even after a reload, and tracks the MIDI
+/// channel of the `mpoly` voice currently running so `bend` inside a voice
+/// body follows its channel.
+///
+/// MPE zones are configured by the controller with the MPE Configuration
+/// Message (RPN 6 on channel 1 or 16), followed here so `mpoly:N:mpe` knows
+/// which member channels belong to which master channel.
+///
+/// Because it is written once per frame, it also measures MIDI clock pulse
+/// spacing in frames for `midicps`, and flags the frame a Start arrived on.
 pub struct MidiFrameEvents {
     len: AtomicUsize,
     events: [AtomicMidiEvent; MAX_MIDI_EVENTS_PER_FRAME],
+    controls: [[AtomicU64; 128]; MIDI_CHANNELS + 1],
+    bends: [AtomicU64; MIDI_CHANNELS + 1],
+    pressures: [AtomicU64; MIDI_CHANNELS + 1],
+    /// Selected RPN per channel, `NULL_RPN` when none.
+    rpns: [AtomicU32; MIDI_CHANNELS],
+    /// Member channel counts of the lower (master 1) and upper (master 16)
+    /// MPE zones.
+    lower_zone: AtomicU32,
+    upper_zone: AtomicU32,
+    /// Channel of the running `mpoly` voice, `u32::MAX` outside voice bodies.
+    voice_channel: AtomicU32,
+    /// MPE master channel of the running voice, `u32::MAX` outside MPE zones.
+    voice_master: AtomicU32,
+    /// Smoothed frames per clock pulse, 0 before two pulses arrived.
+    pulse_frames: AtomicU64,
+    /// `u64::MAX` until the first pulse.
+    frames_since_pulse: AtomicU64,
+    /// Cleared by Stop, set by Start and Continue.
+    clock_running: AtomicBool,
+    /// Start arrived in the current frame.
+    started: AtomicBool,
 }
 
 impl Default for MidiFrameEvents {
@@ -99,6 +326,21 @@ impl Default for MidiFrameEvents {
         MidiFrameEvents {
             len: AtomicUsize::new(0),
             events: std::array::from_fn(|_| AtomicMidiEvent::default()),
+            controls: std::array::from_fn(|_| std::array::from_fn(|_| AtomicU64::new(0))),
+            bends: std::array::from_fn(|_| AtomicU64::new(0)),
+            pressures: std::array::from_fn(|_| AtomicU64::new(0)),
+            rpns: std::array::from_fn(|_| AtomicU32::new(NULL_RPN)),
+            // Without a configuration message, assume the common single
+            // lower zone spanning every channel.
+            lower_zone: AtomicU32::new(15),
+            upper_zone: AtomicU32::new(0),
+            voice_channel: AtomicU32::new(u32::MAX),
+            voice_master: AtomicU32::new(u32::MAX),
+            pulse_frames: AtomicU64::new(0.0f64.to_bits()),
+            frames_since_pulse: AtomicU64::new(u64::MAX),
+            // Clocks that never send Start still drive `midicps`.
+            clock_running: AtomicBool::new(true),
+            started: AtomicBool::new(false),
         }
     }
 }
@@ -111,12 +353,159 @@ impl MidiFrameEvents {
     pub fn set_events(&self, events: &[MidiEvent]) {
         let len = events.len().min(MAX_MIDI_EVENTS_PER_FRAME);
         self.len.store(0, Ordering::Release);
+        let frames = self.frames_since_pulse.load(Ordering::Relaxed);
+        self.frames_since_pulse
+            .store(frames.saturating_add(1), Ordering::Relaxed);
+        self.started.store(false, Ordering::Relaxed);
         for (slot, &event) in self.events.iter().zip(events.iter()).take(len) {
             slot.store(event);
+            let channel = event.channel as usize;
+            match event.kind {
+                MidiEventKind::ControlChange => {
+                    for slot in [channel, OMNI] {
+                        self.controls[slot][event.note as usize]
+                            .store(event.velocity.to_bits(), Ordering::Relaxed);
+                    }
+                    self.track_rpn(event.channel, event.note, event.velocity);
+                }
+                MidiEventKind::PitchBend => {
+                    for slot in [channel, OMNI] {
+                        self.bends[slot].store(event.velocity.to_bits(), Ordering::Relaxed);
+                    }
+                }
+                MidiEventKind::ChannelPressure => {
+                    for slot in [channel, OMNI] {
+                        self.pressures[slot].store(event.velocity.to_bits(), Ordering::Relaxed);
+                    }
+                }
+                MidiEventKind::Clock => self.clock_pulse(),
+                MidiEventKind::Start => {
+                    self.started.store(true, Ordering::Relaxed);
+                    self.clock_running.store(true, Ordering::Relaxed);
+                }
+                MidiEventKind::Continue => self.clock_running.store(true, Ordering::Relaxed),
+                MidiEventKind::Stop => self.clock_running.store(false, Ordering::Relaxed),
+                MidiEventKind::NoteOn | MidiEventKind::NoteOff => {}
+            }
         }
         self.len.store(len, Ordering::Release);
     }
 
+    fn clock_pulse(&self) {
+        let measured = self.frames_since_pulse.swap(0, Ordering::Relaxed);
+        let pulse = Sample::from_bits(self.pulse_frames.load(Ordering::Relaxed));
+        // Skip the first pulse, pulses sharing a frame, and the gap after a
+        // clock restart.
+        if measured == u64::MAX || measured == 0 {
+            return;
+        }
+        let measured = measured as Sample;
+        if pulse > 0.0 && measured > pulse * CLOCK_TIMEOUT_PULSES {
+            return;
+        }
+        let pulse = if pulse > 0.0 {
+            pulse + (measured - pulse) * CLOCK_SMOOTHING
+        } else {
+            measured
+        };
+        self.pulse_frames.store(pulse.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Smoothed MIDI clock pulse interval in frames, `None` while the clock
+    /// is stopped, silent, or hasn't sent two pulses yet.
+    pub fn clock_pulse_frames(&self) -> Option<Sample> {
+        let pulse = Sample::from_bits(self.pulse_frames.load(Ordering::Relaxed));
+        let silent =
+            self.frames_since_pulse.load(Ordering::Relaxed) as f64 > pulse * CLOCK_TIMEOUT_PULSES;
+        (pulse > 0.0 && !silent && self.clock_running.load(Ordering::Relaxed)).then_some(pulse)
+    }
+
+    /// Whether a MIDI Start arrived in the current frame.
+    pub fn clock_started(&self) -> bool {
+        self.started.load(Ordering::Relaxed)
+    }
+
+    /// Latest value `0..1` of `controller`, on `channel` or any channel.
+    pub fn control(&self, channel: Option<u8>, controller: u8) -> Sample {
+        let slot = channel.map_or(OMNI, |channel| channel as usize);
+        Sample::from_bits(self.controls[slot][controller as usize].load(Ordering::Relaxed))
+    }
+
+    /// Latest pitch bend `-1..1`, on `channel` or any channel.
+    pub fn bend(&self, channel: Option<u8>) -> Sample {
+        let slot = channel.map_or(OMNI, |channel| channel as usize);
+        Sample::from_bits(self.bends[slot].load(Ordering::Relaxed))
+    }
+
+    /// Latest channel pressure `0..1`, on `channel` or any channel.
+    pub fn pressure(&self, channel: Option<u8>) -> Sample {
+        let slot = channel.map_or(OMNI, |channel| channel as usize);
+        Sample::from_bits(self.pressures[slot].load(Ordering::Relaxed))
+    }
+
+    /// Follow RPN selection and data entry far enough to apply MPE
+    /// Configuration Messages. Other RPNs are ignored.
+    fn track_rpn(&self, channel: u8, controller: u8, value: Sample) {
+        let value = (value * 127.0).round() as u32;
+        let rpn = &self.rpns[channel as usize];
+        match controller {
+            RPN_MSB => {
+                let lsb = rpn.load(Ordering::Relaxed) & 0x7f;
+                rpn.store((value << 7) | lsb, Ordering::Relaxed);
+            }
+            RPN_LSB => {
+                let msb = rpn.load(Ordering::Relaxed) & !0x7f;
+                rpn.store(msb | value, Ordering::Relaxed);
+            }
+            DATA_ENTRY if rpn.load(Ordering::Relaxed) == MPE_CONFIGURATION => {
+                let members = value.min(15);
+                let (zone, other) = match channel {
+                    0 => (&self.lower_zone, &self.upper_zone),
+                    15 => (&self.upper_zone, &self.lower_zone),
+                    _ => return,
+                };
+                zone.store(members, Ordering::Relaxed);
+                // Zones never overlap: the newly configured one wins.
+                let other_members = other.load(Ordering::Relaxed);
+                other.store(
+                    other_members.min(14u32.saturating_sub(members)),
+                    Ordering::Relaxed,
+                );
+            }
+            _ => {}
+        }
+    }
+
+    /// Master channel of the MPE zone containing `channel`, if any. Master
+    /// channels belong to their own zone.
+    pub fn zone_master(&self, channel: u8) -> Option<u8> {
+        let lower = self.lower_zone.load(Ordering::Relaxed) as u8;
+        let upper = self.upper_zone.load(Ordering::Relaxed) as u8;
+        if lower > 0 && channel <= lower {
+            Some(0)
+        } else if upper > 0 && channel >= 15 - upper {
+            Some(15)
+        } else {
+            None
+        }
+    }
+
+    pub fn voice_channel(&self) -> Option<u8> {
+        u8::try_from(self.voice_channel.load(Ordering::Relaxed)).ok()
+    }
+
+    /// MPE master channel of the running voice, when it belongs to a zone.
+    pub fn voice_master(&self) -> Option<u8> {
+        u8::try_from(self.voice_master.load(Ordering::Relaxed)).ok()
+    }
+
+    fn set_voice(&self, channel: Option<u8>, master: Option<u8>) {
+        self.voice_channel
+            .store(channel.map_or(u32::MAX, u32::from), Ordering::Relaxed);
+        self.voice_master
+            .store(master.map_or(u32::MAX, u32::from), Ordering::Relaxed);
+    }
+
     pub fn clear(&self) {
         self.len.store(0, Ordering::Release);
     }
@@ -133,6 +522,33 @@ impl MidiFrameEvents {
     }
 }
 
+/// Channel set and key range an `mpoly` responds to, as bit masks over
+/// channels `0..16` and notes `0..128`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MidiFilter {
+    channels: u16,
+    notes: u128,
+}
+
+impl Default for MidiFilter {
+    fn default() -> Self {
+        MidiFilter {
+            channels: u16::MAX,
+            notes: u128::MAX,
+        }
+    }
+}
+
+impl MidiFilter {
+    pub fn new(channels: u16, notes: u128) -> Self {
+        MidiFilter { channels, notes }
+    }
+
+    pub fn accepts(&self, channel: u8, note: u8) -> bool {
+        self.channels & (1 << channel.min(15)) != 0 && self.notes & (1 << note.min(127)) != 0
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum VoiceState {
     NeverUsed,
@@ -142,6 +558,7 @@ enum VoiceState {
 
 struct MVoice {
     program: Box<[Statement]>,
+    source: u8,
     channel: u8,
     note: u8,
     velocity: Sample,
@@ -150,12 +567,20 @@ struct MVoice {
     trigger_order: u64,
     release_order: u64,
     pending_retrigger: bool,
+    /// Held only by the sustain pedal: note-off arrived while it was down.
+    sustained: bool,
 }
 
 pub struct MPoly {
     voices: Vec<MVoice>,
     midi: Arc<MidiFrameEvents>,
     order: u64,
+    /// Sustain pedal (CC64) down, per MIDI channel.
+    sustain: [bool; MIDI_CHANNELS],
+    /// MPE mode: only notes inside a zone play, and master channel pedal
+    /// and bend apply to the whole zone.
+    mpe: bool,
+    filter: MidiFilter,
     stack: Stack,
     event_buffer: [MidiEvent; MAX_MIDI_EVENTS_PER_FRAME],
 }
@@ -167,6 +592,7 @@ impl MPoly {
                 .into_iter()
                 .map(|program| MVoice {
                     program,
+                    source: 0,
                     channel: 0,
                     note: 0,
                     velocity: 0.0,
@@ -175,10 +601,14 @@ impl MPoly {
                     trigger_order: 0,
                     release_order: 0,
                     pending_retrigger: false,
+                    sustained: false,
                 })
                 .collect(),
             midi,
             order: 0,
+            sustain: [false; MIDI_CHANNELS],
+            mpe: false,
+            filter: MidiFilter::default(),
             stack: Stack::new(),
             event_buffer: [MidiEvent::note_off(0, 0); MAX_MIDI_EVENTS_PER_FRAME],
         }
@@ -190,6 +620,25 @@ impl MPoly {
         Self::new(Vec::new(), midi)
     }
 
+    pub fn set_mpe(&mut self, mpe: bool) {
+        self.mpe = mpe;
+    }
+
+    /// Only note-ons are filtered: note-offs and pedals only ever affect
+    /// voices this `mpoly` started, and keep working if the filter changes
+    /// under held notes.
+    pub fn set_filter(&mut self, filter: MidiFilter) {
+        self.filter = filter;
+    }
+
+    fn zone_master(&self, channel: u8) -> Option<u8> {
+        if self.mpe {
+            self.midi.zone_master(channel)
+        } else {
+            None
+        }
+    }
+
     fn next_order(&mut self) -> u64 {
         let order = self.order;
         self.order = self.order.wrapping_add(1);
@@ -216,6 +665,16 @@ impl MPoly {
         {
             return Some(index);
         }
+        // Pedal-sustained voices are stolen before keys still held down.
+        if let Some((index, _)) = self
+            .voices
+            .iter()
+            .enumerate()
+            .filter(|(_, voice)| voice.sustained)
+            .min_by_key(|(_, voice)| voice.trigger_order)
+        {
+            return Some(index);
+        }
         self.voices
             .iter()
             .enumerate()
@@ -223,13 +682,17 @@ impl MPoly {
             .map(|(index, _)| index)
     }
 
-    fn note_on(&mut self, channel: u8, note: u8, velocity: Sample) {
+    fn note_on(&mut self, source: u8, channel: u8, note: u8, velocity: Sample) {
+        if self.mpe && self.zone_master(channel).is_none() {
+            return;
+        }
         let Some(index) = self.allocate_voice() else {
             return;
         };
         let was_held = self.voices[index].state == VoiceState::Held;
         let order = self.next_order();
         let voice = &mut self.voices[index];
+        voice.source = source;
         voice.channel = channel;
         voice.note = note;
         voice.velocity = velocity.clamp(0.0, 1.0);
@@ -238,26 +701,62 @@ impl MPoly {
         voice.state = VoiceState::Held;
         voice.gate = if was_held { 0.0 } else { voice.velocity };
         voice.pending_retrigger = was_held;
+        voice.sustained = false;
     }
 
-    fn note_off(&mut self, channel: u8, note: u8) {
+    fn note_off(&mut self, source: u8, channel: u8, note: u8) {
         let Some((index, _)) = self
             .voices
             .iter()
             .enumerate()
             .filter(|(_, voice)| {
-                voice.state == VoiceState::Held && voice.channel == channel && voice.note == note
+                voice.state == VoiceState::Held
+                    && !voice.sustained
+                    && voice.source == source
+                    && voice.channel == channel
+                    && voice.note == note
             })
             .min_by_key(|(_, voice)| voice.trigger_order)
         else {
             return;
         };
+        if self.pedal_down(channel) {
+            self.voices[index].sustained = true;
+        } else {
+            self.release(index);
+        }
+    }
+
+    fn release(&mut self, index: usize) {
         let order = self.next_order();
         let voice = &mut self.voices[index];
         voice.gate = 0.0;
         voice.state = VoiceState::Released;
         voice.release_order = order;
         voice.pending_retrigger = false;
+        voice.sustained = false;
+    }
+
+    /// The voice channel's own pedal, or in MPE mode its zone master's.
+    fn pedal_down(&self, channel: u8) -> bool {
+        self.sustain[channel as usize]
+            || self
+                .zone_master(channel)
+                .is_some_and(|master| self.sustain[master as usize])
+    }
+
+    /// Pedal up releases every voice it was holding on that channel.
+    fn set_sustain(&mut self, channel: u8, down: bool) {
+        self.sustain[channel as usize] = down;
+        if down {
+            return;
+        }
+        for index in 0..self.voices.len() {
+            let voice = &self.voices[index];
+            if voice.sustained && !self.pedal_down(voice.channel) {
+                self.release(index);
+            }
+        }
     }
 
     fn process_events(&mut self) {
@@ -272,12 +771,18 @@ impl MPoly {
         for index in 0..event_count {
             let event = self.event_buffer[index];
             match event.kind {
+                MidiEventKind::NoteOn
+                    if event.velocity > 0.0 && !self.filter.accepts(event.channel, event.note) => {}
                 MidiEventKind::NoteOn if event.velocity > 0.0 => {
-                    self.note_on(event.channel, event.note, event.velocity)
+                    self.note_on(event.source, event.channel, event.note, event.velocity)
                 }
                 MidiEventKind::NoteOn | MidiEventKind::NoteOff => {
-                    self.note_off(event.channel, event.note)
+                    self.note_off(event.source, event.channel, event.note)
                 }
+                MidiEventKind::ControlChange if event.note == SUSTAIN_PEDAL => {
+                    self.set_sustain(event.channel, event.velocity >= 0.5)
+                }
+                _ => {}
             }
         }
     }
@@ -286,6 +791,7 @@ impl MPoly {
 impl Op for MPoly {
     fn perform(&mut self, stack: &mut Stack) {
         self.process_events();
+        let outer = (self.midi.voice_channel(), self.midi.voice_master());
         let mut sum = SILENCE;
         for voice in &mut self.voices {
             if voice.state == VoiceState::NeverUsed {
@@ -294,6 +800,12 @@ impl Op for MPoly {
             self.stack.reset();
             self.stack.push(&[voice.note as Sample; CHANNELS]);
             self.stack.push(&[voice.gate; CHANNELS]);
+            let master = if self.mpe {
+                self.midi.zone_master(voice.channel)
+            } else {
+                None
+            };
+            self.midi.set_voice(Some(voice.channel), master);
             for stmt in voice.program.iter_mut() {
                 stmt.op.perform(&mut self.stack);
             }
@@ -302,13 +814,16 @@ impl Op for MPoly {
                 *sum += x;
             }
         }
+        self.midi.set_voice(outer.0, outer.1);
         stack.push(&sum);
     }
 
     fn migrate(&mut self, other: &mut dyn Op) {
         if let Some(other) = other.downcast_mut::<Self>() {
             self.order = other.order;
+            self.sustain = other.sustain;
             for (voice, other_voice) in self.voices.iter_mut().zip(other.voices.iter_mut()) {
+                voice.source = other_voice.source;
                 voice.channel = other_voice.channel;
                 voice.note = other_voice.note;
                 voice.velocity = other_voice.velocity;
@@ -317,12 +832,382 @@ impl Op for MPoly {
                 voice.trigger_order = other_voice.trigger_order;
                 voice.release_order = other_voice.release_order;
                 voice.pending_retrigger = other_voice.pending_retrigger;
+                voice.sustained = other_voice.sustained;
                 migrate_program_state(&mut voice.program, &mut other_voice.program);
             }
         }
     }
 }
 
+/// Which held key a monophonic `mmono` plays.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NotePriority {
+    Last,
+    Low,
+    High,
+}
+
+/// Held keys tracked by `mmono`; the oldest is forgotten beyond this.
+const MAX_HELD_NOTES: usize = 128;
+
+#[derive(Clone, Copy)]
+struct HeldNote {
+    source: u8,
+    channel: u8,
+    note: u8,
+    velocity: Sample,
+}
+
+impl HeldNote {
+    fn is(&self, source: u8, channel: u8, note: u8) -> bool {
+        self.source == source && self.channel == channel && self.note == note
+    }
+}
+
+/// Monophonic MIDI voice with a held-note stack: playing legato moves the
+/// note without retriggering the gate (gliding when a glide time is set),
+/// and releasing a key falls back to the best remaining held key.
+pub struct MMono {
+    program: Box<[Statement]>,
+    midi: Arc<MidiFrameEvents>,
+    priority: NotePriority,
+    filter: MidiFilter,
+    /// One-pole glide coefficient, 0 for none.
+    glide: Sample,
+    /// Held keys in press order.
+    held: Vec<HeldNote>,
+    channel: u8,
+    note: Sample,
+    target: Sample,
+    gate: Sample,
+    stack: Stack,
+    event_buffer: [MidiEvent; MAX_MIDI_EVENTS_PER_FRAME],
+}
+
+impl MMono {
+    pub fn new(
+        program: Box<[Statement]>,
+        midi: Arc<MidiFrameEvents>,
+        priority: NotePriority,
+        glide: Sample,
+        sample_rate: u32,
+    ) -> Self {
+        let glide = if glide > 0.0 {
+            (-1.0 / (glide * sample_rate as Sample)).exp()
+        } else {
+            0.0
+        };
+        MMono {
+            program,
+            midi,
+            priority,
+            filter: MidiFilter::default(),
+            glide,
+            held: Vec::with_capacity(MAX_HELD_NOTES),
+            channel: 0,
+            note: 0.0,
+            target: 0.0,
+            gate: 0.0,
+            stack: Stack::new(),
+            event_buffer: [MidiEvent::note_off(0, 0); MAX_MIDI_EVENTS_PER_FRAME],
+        }
+    }
+
+    /// Forgiving zero-output op for invalid quotations/arguments; like
+    /// `mpoly` it consumes no stack input.
+    pub fn empty(midi: Arc<MidiFrameEvents>) -> Self {
+        Self::new(Box::new([]), midi, NotePriority::Last, 0.0, 48000)
+    }
+
+    pub fn set_filter(&mut self, filter: MidiFilter) {
+        self.filter = filter;
+    }
+
+    fn selected(&self) -> Option<HeldNote> {
+        match self.priority {
+            NotePriority::Last => self.held.last().copied(),
+            NotePriority::Low => self.held.iter().min_by_key(|held| held.note).copied(),
+            NotePriority::High => self.held.iter().max_by_key(|held| held.note).copied(),
+        }
+    }
+
+    fn note_on(&mut self, source: u8, channel: u8, note: u8, velocity: Sample) {
+        self.held.retain(|held| !held.is(source, channel, note));
+        if self.held.len() == MAX_HELD_NOTES {
+            self.held.remove(0);
+        }
+        let legato = !self.held.is_empty();
+        self.held.push(HeldNote {
+            source,
+            channel,
+            note,
+            velocity: velocity.clamp(0.0, 1.0),
+        });
+        let Some(selected) = self.selected() else {
+            return;
+        };
+        self.channel = selected.channel;
+        self.target = selected.note as Sample;
+        if !legato {
+            // A new phrase starts on pitch; only legato moves glide.
+            self.note = self.target;
+            self.gate = selected.velocity;
+        }
+    }
+
+    fn note_off(&mut self, source: u8, channel: u8, note: u8) {
+        self.held.retain(|held| !held.is(source, channel, note));
+        match self.selected() {
+            Some(selected) => {
+                self.channel = selected.channel;
+                self.target = selected.note as Sample;
+            }
+            None => self.gate = 0.0,
+        }
+    }
+
+    fn process_events(&mut self) {
+        let event_count = self.midi.copy_events(&mut self.event_buffer);
+        for index in 0..event_count {
+            let event = self.event_buffer[index];
+            match event.kind {
+                MidiEventKind::NoteOn
+                    if event.velocity > 0.0 && !self.filter.accepts(event.channel, event.note) => {}
+                MidiEventKind::NoteOn if event.velocity > 0.0 => {
+                    self.note_on(event.source, event.channel, event.note, event.velocity)
+                }
+                MidiEventKind::NoteOn | MidiEventKind::NoteOff => {
+                    self.note_off(event.source, event.channel, event.note)
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Op for MMono {
+    fn perform(&mut self, stack: &mut Stack) {
+        self.process_events();
+        if self.program.is_empty() {
+            stack.push(&SILENCE);
+            return;
+        }
+        self.note = self.target + self.glide * (self.note - self.target);
+        let outer = (self.midi.voice_channel(), self.midi.voice_master());
+        self.stack.reset();
+        self.stack.push(&[self.note; CHANNELS]);
+        self.stack.push(&[self.gate; CHANNELS]);
+        self.midi.set_voice(Some(self.channel), None);
+        for stmt in self.program.iter_mut() {
+            stmt.op.perform(&mut self.stack);
+        }
+        self.midi.set_voice(outer.0, outer.1);
+        stack.push(&self.stack.peek());
+    }
+
+    fn migrate(&mut self, other: &mut dyn Op) {
+        if let Some(other) = other.downcast_mut::<Self>() {
+            // Swapping keeps both buffers allocated off the audio thread.
+            std::mem::swap(&mut self.held, &mut other.held);
+            self.channel = other.channel;
+            self.note = other.note;
+            self.gate = other.gate;
+            // A priority change takes effect on the held keys right away.
+            match self.selected() {
+                Some(selected) => self.target = selected.note as Sample,
+                None => self.target = other.target,
+            }
+            migrate_program_state(&mut self.program, &mut other.program);
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum ControlSource {
+    /// Controller number and channel (`None` = any channel).
+    Control(u8, Option<u8>),
+    /// Range in semitones; follows the running `mpoly` voice's channel.
+    Bend(Sample),
+    /// Per-note bend range in semitones; adds the zone master's bend.
+    VoiceBend(Sample),
+    VoicePressure,
+    VoiceTimbre,
+}
+
+/// Bend range of an MPE master channel, the MPE default.
+const MASTER_BEND_RANGE: Sample = 2.0;
+
+/// Latest MIDI controller value as a signal, with optional one-pole
+/// smoothing against zipper noise.
+pub struct MidiControl {
+    midi: Arc<MidiFrameEvents>,
+    source: ControlSource,
+    /// One-pole coefficient, 0 for no smoothing.
+    smoothing: Sample,
+    value: Option<Sample>,
+}
+
+impl MidiControl {
+    /// `cc:N[:CH[:SMOOTH]]` — controller value `0..1`.
+    pub fn control(
+        midi: Arc<MidiFrameEvents>,
+        controller: u8,
+        channel: Option<u8>,
+        smoothing: Sample,
+        sample_rate: u32,
+    ) -> Self {
+        Self::with_source(
+            midi,
+            ControlSource::Control(controller.min(127), channel.map(|ch| ch.min(15))),
+            smoothing,
+            sample_rate,
+        )
+    }
+
+    /// `bend[:RANGE[:SMOOTH]]` — pitch bend in semitones.
+    pub fn bend(
+        midi: Arc<MidiFrameEvents>,
+        range: Sample,
+        smoothing: Sample,
+        sample_rate: u32,
+    ) -> Self {
+        Self::with_source(midi, ControlSource::Bend(range), smoothing, sample_rate)
+    }
+
+    /// `vbend[:RANGE[:SMOOTH]]` — the running voice's per-note bend in
+    /// semitones, plus its MPE zone master's bend over 2 semitones.
+    pub fn voice_bend(
+        midi: Arc<MidiFrameEvents>,
+        range: Sample,
+        smoothing: Sample,
+        sample_rate: u32,
+    ) -> Self {
+        Self::with_source(
+            midi,
+            ControlSource::VoiceBend(range),
+            smoothing,
+            sample_rate,
+        )
+    }
+
+    /// `vpress[:SMOOTH]` — the running voice's channel pressure `0..1`.
+    pub fn voice_pressure(midi: Arc<MidiFrameEvents>, smoothing: Sample, sample_rate: u32) -> Self {
+        Self::with_source(midi, ControlSource::VoicePressure, smoothing, sample_rate)
+    }
+
+    /// `vtimbre[:SMOOTH]` — the running voice's CC74 `0..1`.
+    pub fn voice_timbre(midi: Arc<MidiFrameEvents>, smoothing: Sample, sample_rate: u32) -> Self {
+        Self::with_source(midi, ControlSource::VoiceTimbre, smoothing, sample_rate)
+    }
+
+    fn with_source(
+        midi: Arc<MidiFrameEvents>,
+        source: ControlSource,
+        smoothing: Sample,
+        sample_rate: u32,
+    ) -> Self {
+        let smoothing = if smoothing > 0.0 {
+            (-1.0 / (smoothing * sample_rate as Sample)).exp()
+        } else {
+            0.0
+        };
+        MidiControl {
+            midi,
+            source,
+            smoothing,
+            value: None,
+        }
+    }
+}
+
+impl Op for MidiControl {
+    fn perform(&mut self, stack: &mut Stack) {
+        let target = match self.source {
+            ControlSource::Control(controller, channel) => self.midi.control(channel, controller),
+            ControlSource::Bend(range) => self.midi.bend(self.midi.voice_channel()) * range,
+            // Voice-scoped sources are silent outside `mpoly` voice bodies.
+            ControlSource::VoiceBend(range) => match self.midi.voice_channel() {
+                Some(channel) => {
+                    let master = self
+                        .midi
+                        .voice_master()
+                        .filter(|&master| master != channel)
+                        .map_or(0.0, |master| {
+                            self.midi.bend(Some(master)) * MASTER_BEND_RANGE
+                        });
+                    self.midi.bend(Some(channel)) * range + master
+                }
+                None => 0.0,
+            },
+            ControlSource::VoicePressure => self
+                .midi
+                .voice_channel()
+                .map_or(0.0, |channel| self.midi.pressure(Some(channel))),
+            ControlSource::VoiceTimbre => self
+                .midi
+                .voice_channel()
+                .map_or(0.0, |channel| self.midi.control(Some(channel), TIMBRE)),
+        };
+        // Start from the current position instead of gliding up from 0.
+        let value = self
+            .value
+            .map_or(target, |y| target + self.smoothing * (y - target));
+        self.value = Some(value);
+        stack.push(&[value; CHANNELS]);
+    }
+
+    fn migrate(&mut self, other: &mut dyn Op) {
+        if let Some(other) = other.downcast_mut::<Self>() {
+            self.value = other.value;
+        }
+    }
+}
+
+/// `midicps[:PPC]` — tempo of an external MIDI clock in cycles per second,
+/// one cycle lasting `PPC` clock pulses; 0 while the clock is stopped.
+pub struct MidiCps {
+    midi: Arc<MidiFrameEvents>,
+    /// Frames per second over pulses per cycle.
+    scale: Sample,
+}
+
+impl MidiCps {
+    pub fn new(midi: Arc<MidiFrameEvents>, pulses_per_cycle: u32, sample_rate: u32) -> Self {
+        MidiCps {
+            midi,
+            scale: sample_rate as Sample / pulses_per_cycle.max(1) as Sample,
+        }
+    }
+}
+
+impl Op for MidiCps {
+    fn perform(&mut self, stack: &mut Stack) {
+        let cps = self
+            .midi
+            .clock_pulse_frames()
+            .map_or(0.0, |frames| self.scale / frames);
+        stack.push(&[cps; CHANNELS]);
+    }
+}
+
+/// `midistart` — 1 on the frame a MIDI Start arrives, 0 otherwise.
+pub struct MidiStart {
+    midi: Arc<MidiFrameEvents>,
+}
+
+impl MidiStart {
+    pub fn new(midi: Arc<MidiFrameEvents>) -> Self {
+        MidiStart { midi }
+    }
+}
+
+impl Op for MidiStart {
+    fn perform(&mut self, stack: &mut Stack) {
+        let started = if self.midi.clock_started() { 1.0 } else { 0.0 };
+        stack.push(&[started; CHANNELS]);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -410,6 +1295,24 @@ mod tests {
         );
     }
 
+    #[test]
+    fn note_off_only_releases_notes_from_its_own_input() {
+        let midi = Arc::new(MidiFrameEvents::new());
+        let mut mpoly = probe_mpoly(2, Arc::clone(&midi));
+        frame(&mut mpoly, &midi, &[MidiEvent::note_on(0, 60, 0.5)]);
+        let second = MidiEvent::note_on(0, 60, 0.25).with_source(1);
+        frame(&mut mpoly, &midi, &[second]);
+        // The first input's voice stays held, not the oldest matching one.
+        assert_eq!(
+            frame(
+                &mut mpoly,
+                &midi,
+                &[MidiEvent::note_off(0, 60).with_source(1)]
+            ),
+            [170.0, 170.0]
+        );
+    }
+
     #[test]
     fn stealing_forces_one_sample_retrigger() {
         let midi = Arc::new(MidiFrameEvents::new());
@@ -422,6 +1325,466 @@ mod tests {
         assert_eq!(frame(&mut mpoly, &midi, &[]), [152.0, 152.0]);
     }
 
+    #[test]
+    fn sustain_pedal_defers_note_offs_until_it_lifts() {
+        let midi = Arc::new(MidiFrameEvents::new());
+        let mut mpoly = probe_mpoly(2, Arc::clone(&midi));
+        let pedal = |down: bool| MidiEvent::control_change(0, SUSTAIN_PEDAL, down as u8 as Sample);
+        frame(
+            &mut mpoly,
+            &midi,
+            &[pedal(true), MidiEvent::note_on(0, 60, 0.5)],
+        );
+        // Released key keeps sounding under the pedal.
+        assert_eq!(
+            frame(&mut mpoly, &midi, &[MidiEvent::note_off(0, 60)]),
+            [110.0, 110.0]
+        );
+        // The pedal on another channel doesn't release it.
+        assert_eq!(
+            frame(
+                &mut mpoly,
+                &midi,
+                &[MidiEvent::control_change(1, SUSTAIN_PEDAL, 0.0)]
+            ),
+            [110.0, 110.0]
+        );
+        assert_eq!(frame(&mut mpoly, &midi, &[pedal(false)]), [60.0, 60.0]);
+    }
+
+    #[test]
+    fn sustained_note_retriggered_under_pedal_gets_a_new_voice() {
+        let midi = Arc::new(MidiFrameEvents::new());
+        let mut mpoly = probe_mpoly(2, Arc::clone(&midi));
+        let pedal = |down: bool| MidiEvent::control_change(0, SUSTAIN_PEDAL, down as u8 as Sample);
+        frame(
+            &mut mpoly,
+            &midi,
+            &[pedal(true), MidiEvent::note_on(0, 60, 0.5)],
+        );
+        frame(&mut mpoly, &midi, &[MidiEvent::note_off(0, 60)]);
+        // Both strikes ring; lifting the pedal after the second key-up
+        // releases both.
+        assert_eq!(
+            frame(&mut mpoly, &midi, &[MidiEvent::note_on(0, 60, 0.25)]),
+            [195.0, 195.0]
+        );
+        frame(&mut mpoly, &midi, &[MidiEvent::note_off(0, 60)]);
+        assert_eq!(frame(&mut mpoly, &midi, &[pedal(false)]), [120.0, 120.0]);
+    }
+
+    #[test]
+    fn migrate_preserves_sustain_pedal_state() {
+        let midi = Arc::new(MidiFrameEvents::new());
+        let mut old = probe_mpoly(1, Arc::clone(&midi));
+        frame(
+            &mut old,
+            &midi,
+            &[
+                MidiEvent::control_change(3, SUSTAIN_PEDAL, 1.0),
+                MidiEvent::note_on(3, 60, 1.0),
+            ],
+        );
+        let mut new = probe_mpoly(1, Arc::clone(&midi));
+        new.migrate(&mut old);
+        assert_eq!(
+            frame(&mut new, &midi, &[MidiEvent::note_off(3, 60)]),
+            [160.0, 160.0]
+        );
+        assert_eq!(
+            frame(
+                &mut new,
+                &midi,
+                &[MidiEvent::control_change(3, SUSTAIN_PEDAL, 0.0)]
+            ),
+            [60.0, 60.0]
+        );
+    }
+
+    fn read(op: &mut MidiControl) -> Sample {
+        let mut stack = Stack::new();
+        op.perform(&mut stack);
+        stack.pop()[0]
+    }
+
+    #[test]
+    fn controls_latch_per_channel_and_omni() {
+        let midi = Arc::new(MidiFrameEvents::new());
+        let mut omni = MidiControl::control(Arc::clone(&midi), 74, None, 0.0, 48000);
+        let mut second = MidiControl::control(Arc::clone(&midi), 74, Some(1), 0.0, 48000);
+        midi.set_events(&[MidiEvent::control_change(1, 74, 0.5)]);
+        assert_eq!((read(&mut omni), read(&mut second)), (0.5, 0.5));
+        // Values persist after the frame and other channels only move omni.
+        midi.set_events(&[MidiEvent::control_change(2, 74, 1.0)]);
+        midi.clear();
+        assert_eq!((read(&mut omni), read(&mut second)), (1.0, 0.5));
+    }
+
+    #[test]
+    fn control_smoothing_glides_towards_new_values() {
+        let midi = Arc::new(MidiFrameEvents::new());
+        let mut wheel = MidiControl::control(Arc::clone(&midi), MOD_WHEEL, None, 0.01, 1000);
+        assert_eq!(read(&mut wheel), 0.0);
+        midi.set_events(&[MidiEvent::control_change(0, MOD_WHEEL, 1.0)]);
+        let first = read(&mut wheel);
+        assert!(first > 0.0 && first < 0.2, "{first}");
+        for _ in 0..100 {
+            read(&mut wheel);
+        }
+        assert!(read(&mut wheel) > 0.99);
+    }
+
+    /// Pops gate and note, pushes `note + bend`.
+    struct BendProbe(MidiControl);
+
+    impl Op for BendProbe {
+        fn perform(&mut self, stack: &mut Stack) {
+            stack.pop();
+            let note = stack.pop();
+            self.0.perform(stack);
+            let bend = stack.pop();
+            stack.push(&[note[0] + bend[0]; CHANNELS]);
+        }
+    }
+
+    #[test]
+    fn bend_follows_each_mpoly_voice_channel() {
+        let midi = Arc::new(MidiFrameEvents::new());
+        let mut mpoly = MPoly::new(
+            (0..2)
+                .map(|_| {
+                    body(Box::new(BendProbe(MidiControl::bend(
+                        Arc::clone(&midi),
+                        2.0,
+                        0.0,
+                        48000,
+                    ))) as Box<dyn Op>)
+                })
+                .collect(),
+            Arc::clone(&midi),
+        );
+        frame(
+            &mut mpoly,
+            &midi,
+            &[
+                MidiEvent::note_on(0, 60, 1.0),
+                MidiEvent::note_on(1, 64, 1.0),
+                MidiEvent::pitch_bend(1, 0.5),
+            ],
+        );
+        // Only the channel 1 voice bends by a semitone.
+        assert_eq!(frame(&mut mpoly, &midi, &[]), [125.0, 125.0]);
+        // Outside voice bodies bend reads the latest bend on any channel.
+        let mut bend = MidiControl::bend(Arc::clone(&midi), 12.0, 0.0, 48000);
+        assert_eq!(read(&mut bend), 6.0);
+    }
+
+    /// MPE Configuration Message: RPN 6 data entry with the member count.
+    fn mpe_configuration(master: u8, members: u8) -> [MidiEvent; 3] {
+        let value = |x: u8| x as Sample / 127.0;
+        [
+            MidiEvent::control_change(master, RPN_MSB, 0.0),
+            MidiEvent::control_change(master, RPN_LSB, value(6)),
+            MidiEvent::control_change(master, DATA_ENTRY, value(members)),
+        ]
+    }
+
+    #[test]
+    fn mpe_configuration_messages_set_non_overlapping_zones() {
+        let midi = MidiFrameEvents::new();
+        assert_eq!(
+            (midi.zone_master(0), midi.zone_master(15)),
+            (Some(0), Some(0))
+        );
+        midi.set_events(&mpe_configuration(0, 7));
+        assert_eq!((midi.zone_master(7), midi.zone_master(8)), (Some(0), None));
+        // A large upper zone shrinks the lower one.
+        midi.set_events(&mpe_configuration(15, 10));
+        assert_eq!(
+            (
+                midi.zone_master(4),
+                midi.zone_master(5),
+                midi.zone_master(6)
+            ),
+            (Some(0), Some(15), Some(15))
+        );
+        // Data entry for other RPNs leaves zones alone.
+        midi.set_events(&[
+            MidiEvent::control_change(0, RPN_LSB, 0.0),
+            MidiEvent::control_change(0, DATA_ENTRY, 1.0),
+        ]);
+        assert_eq!(midi.zone_master(4), Some(0));
+    }
+
+    /// Pushes `vbend + 10 * vpress + 100 * vtimbre`.
+    struct ExpressionProbe([MidiControl; 3]);
+
+    impl Op for ExpressionProbe {
+        fn perform(&mut self, stack: &mut Stack) {
+            stack.pop();
+            stack.pop();
+            let mut sum = 0.0;
+            for (control, scale) in self.0.iter_mut().zip([1.0, 10.0, 100.0]) {
+                control.perform(stack);
+                sum += stack.pop()[0] * scale;
+            }
+            stack.push(&[sum; CHANNELS]);
+        }
+    }
+
+    fn expression_mpoly(voices: usize, midi: &Arc<MidiFrameEvents>) -> MPoly {
+        let mut mpoly = MPoly::new(
+            (0..voices)
+                .map(|_| {
+                    body(Box::new(ExpressionProbe([
+                        MidiControl::voice_bend(Arc::clone(midi), 48.0, 0.0, 48000),
+                        MidiControl::voice_pressure(Arc::clone(midi), 0.0, 48000),
+                        MidiControl::voice_timbre(Arc::clone(midi), 0.0, 48000),
+                    ])) as Box<dyn Op>)
+                })
+                .collect(),
+            Arc::clone(midi),
+        );
+        mpoly.set_mpe(true);
+        mpoly
+    }
+
+    #[test]
+    fn mpe_voices_read_their_own_channel_expression() {
+        let midi = Arc::new(MidiFrameEvents::new());
+        let mut mpoly = expression_mpoly(2, &midi);
+        frame(
+            &mut mpoly,
+            &midi,
+            &[
+                MidiEvent::note_on(1, 60, 1.0),
+                MidiEvent::note_on(2, 64, 1.0),
+                MidiEvent::pitch_bend(1, 0.25),
+                MidiEvent::channel_pressure(2, 0.5),
+                MidiEvent::control_change(2, TIMBRE, 0.5),
+            ],
+        );
+        assert_eq!(frame(&mut mpoly, &midi, &[]), [12.0 + 5.0 + 50.0; CHANNELS]);
+        // The master channel bend moves every voice in the zone.
+        assert_eq!(
+            frame(&mut mpoly, &midi, &[MidiEvent::pitch_bend(0, 0.5)]),
+            [67.0 + 2.0; CHANNELS]
+        );
+        // Outside voice bodies the voice-scoped ops read 0.
+        let mut pressure = MidiControl::voice_pressure(Arc::clone(&midi), 0.0, 48000);
+        assert_eq!(read(&mut pressure), 0.0);
+    }
+
+    #[test]
+    fn mpe_mode_ignores_notes_outside_zones_and_sustains_from_master() {
+        let midi = Arc::new(MidiFrameEvents::new());
+        midi.set_events(&mpe_configuration(0, 3));
+        let mut mpoly = probe_mpoly(2, Arc::clone(&midi));
+        mpoly.set_mpe(true);
+        assert_eq!(
+            frame(&mut mpoly, &midi, &[MidiEvent::note_on(5, 60, 1.0)]),
+            [0.0, 0.0]
+        );
+        frame(
+            &mut mpoly,
+            &midi,
+            &[
+                MidiEvent::control_change(0, SUSTAIN_PEDAL, 1.0),
+                MidiEvent::note_on(2, 60, 1.0),
+            ],
+        );
+        assert_eq!(
+            frame(&mut mpoly, &midi, &[MidiEvent::note_off(2, 60)]),
+            [160.0, 160.0]
+        );
+        assert_eq!(
+            frame(
+                &mut mpoly,
+                &midi,
+                &[MidiEvent::control_change(0, SUSTAIN_PEDAL, 0.0)]
+            ),
+            [60.0, 60.0]
+        );
+    }
+
+    #[test]
+    fn filter_splits_keyboard_by_channel_and_key_range() {
+        let midi = Arc::new(MidiFrameEvents::new());
+        let mut bass = probe_mpoly(1, Arc::clone(&midi));
+        bass.set_filter(MidiFilter::new(1, (1 << 60) - 1));
+        let mut lead = probe_mpoly(1, Arc::clone(&midi));
+        lead.set_filter(MidiFilter::new(1, !((1 << 60) - 1)));
+        let events = [
+            MidiEvent::note_on(0, 40, 1.0),
+            MidiEvent::note_on(0, 72, 1.0),
+        ];
+        assert_eq!(frame(&mut bass, &midi, &events), [140.0, 140.0]);
+        assert_eq!(frame(&mut lead, &midi, &events), [172.0, 172.0]);
+        // Other channels are ignored entirely.
+        assert_eq!(
+            frame(&mut bass, &midi, &[MidiEvent::note_on(1, 41, 1.0)]),
+            [140.0, 140.0]
+        );
+        // Note-offs always pass, so a filter edit can't strand held notes.
+        bass.set_filter(MidiFilter::new(2, 0));
+        assert_eq!(
+            frame(&mut bass, &midi, &[MidiEvent::note_off(0, 40)]),
+            [40.0, 40.0]
+        );
+    }
+
+    fn probe_mmono(priority: NotePriority, glide: Sample, midi: &Arc<MidiFrameEvents>) -> MMono {
+        MMono::new(
+            body(Box::new(Probe) as Box<dyn Op>),
+            Arc::clone(midi),
+            priority,
+            glide,
+            1000,
+        )
+    }
+
+    fn mono_frame(mmono: &mut MMono, midi: &MidiFrameEvents, events: &[MidiEvent]) -> Sample {
+        midi.set_events(events);
+        let mut stack = Stack::new();
+        mmono.perform(&mut stack);
+        stack.pop()[0]
+    }
+
+    #[test]
+    fn mmono_plays_legato_and_falls_back_to_held_notes() {
+        let midi = Arc::new(MidiFrameEvents::new());
+        let mut mmono = probe_mmono(NotePriority::Last, 0.0, &midi);
+        assert_eq!(
+            mono_frame(&mut mmono, &midi, &[MidiEvent::note_on(0, 60, 0.5)]),
+            110.0
+        );
+        // Legato keeps the phrase's gate and velocity.
+        assert_eq!(
+            mono_frame(&mut mmono, &midi, &[MidiEvent::note_on(0, 67, 1.0)]),
+            117.0
+        );
+        assert_eq!(
+            mono_frame(&mut mmono, &midi, &[MidiEvent::note_off(0, 67)]),
+            110.0
+        );
+        assert_eq!(
+            mono_frame(&mut mmono, &midi, &[MidiEvent::note_off(0, 60)]),
+            60.0
+        );
+        assert_eq!(
+            mono_frame(&mut mmono, &midi, &[MidiEvent::note_on(0, 62, 1.0)]),
+            162.0
+        );
+    }
+
+    #[test]
+    fn mmono_note_priority_picks_among_held_keys() {
+        let midi = Arc::new(MidiFrameEvents::new());
+        let chord = [
+            MidiEvent::note_on(0, 64, 1.0),
+            MidiEvent::note_on(0, 48, 1.0),
+            MidiEvent::note_on(0, 55, 1.0),
+        ];
+        for (priority, note) in [
+            (NotePriority::Last, 155.0),
+            (NotePriority::Low, 148.0),
+            (NotePriority::High, 164.0),
+        ] {
+            let mut mmono = probe_mmono(priority, 0.0, &midi);
+            assert_eq!(mono_frame(&mut mmono, &midi, &chord), note, "{priority:?}");
+        }
+        let mut low = probe_mmono(NotePriority::Low, 0.0, &midi);
+        mono_frame(&mut low, &midi, &chord);
+        assert_eq!(
+            mono_frame(&mut low, &midi, &[MidiEvent::note_off(0, 48)]),
+            155.0
+        );
+    }
+
+    #[test]
+    fn mmono_glides_only_between_legato_notes() {
+        let midi = Arc::new(MidiFrameEvents::new());
+        let mut mmono = probe_mmono(NotePriority::Last, 0.01, &midi);
+        assert_eq!(
+            mono_frame(&mut mmono, &midi, &[MidiEvent::note_on(0, 60, 1.0)]),
+            160.0
+        );
+        let gliding = mono_frame(&mut mmono, &midi, &[MidiEvent::note_on(0, 72, 1.0)]);
+        assert!(gliding > 160.0 && gliding < 162.0, "{gliding}");
+        for _ in 0..100 {
+            mono_frame(&mut mmono, &midi, &[]);
+        }
+        assert!(mono_frame(&mut mmono, &midi, &[]) > 171.99);
+    }
+
+    #[test]
+    fn mmono_migrate_keeps_held_notes() {
+        let midi = Arc::new(MidiFrameEvents::new());
+        let mut old = probe_mmono(NotePriority::Last, 0.0, &midi);
+        mono_frame(
+            &mut old,
+            &midi,
+            &[
+                MidiEvent::note_on(0, 50, 1.0),
+                MidiEvent::note_on(0, 70, 1.0),
+            ],
+        );
+        let mut new = probe_mmono(NotePriority::Low, 0.0, &midi);
+        new.migrate(&mut old);
+        assert_eq!(mono_frame(&mut new, &midi, &[]), 150.0);
+        assert_eq!(
+            mono_frame(&mut new, &midi, &[MidiEvent::note_off(0, 50)]),
+            170.0
+        );
+    }
+
+    #[test]
+    fn empty_mmono_pushes_silence() {
+        let midi = Arc::new(MidiFrameEvents::new());
+        let mut mmono = MMono::empty(Arc::clone(&midi));
+        assert_eq!(
+            mono_frame(&mut mmono, &midi, &[MidiEvent::note_on(0, 60, 1.0)]),
+            0.0
+        );
+    }
+
+    #[test]
+    fn midi_clock_tempo_follows_pulse_spacing_and_transport() {
+        let midi = Arc::new(MidiFrameEvents::new());
+        let mut cps = MidiCps::new(Arc::clone(&midi), 4, 1000);
+        let mut start = MidiStart::new(Arc::clone(&midi));
+        let run = |frames: usize, events: &[MidiEvent]| {
+            midi.set_events(events);
+            for _ in 1..frames {
+                midi.set_events(&[]);
+            }
+        };
+        let clock = MidiEvent::realtime(MidiEventKind::Clock);
+        run(1, &[MidiEvent::realtime(MidiEventKind::Start), clock]);
+        let mut stack = Stack::new();
+        start.perform(&mut stack);
+        assert_eq!(stack.pop()[0], 1.0);
+        run(24, &[]);
+        // Pulses every 25 frames at 4 pulses per cycle: 10 cycles per second.
+        for _ in 0..4 {
+            run(25, &[clock]);
+        }
+        start.perform(&mut stack);
+        cps.perform(&mut stack);
+        assert_eq!(stack.pop()[0], 10.0);
+        assert_eq!(stack.pop()[0], 0.0);
+        run(1, &[MidiEvent::realtime(MidiEventKind::Stop)]);
+        cps.perform(&mut stack);
+        assert_eq!(stack.pop()[0], 0.0);
+        // Continue resumes without a start trigger; a silent clock stops.
+        run(1, &[MidiEvent::realtime(MidiEventKind::Continue)]);
+        cps.perform(&mut stack);
+        assert_eq!(stack.pop()[0], 10.0);
+        run(200, &[]);
+        cps.perform(&mut stack);
+        assert_eq!(stack.pop()[0], 0.0);
+    }
+
     #[test]
     fn empty_mpoly_consumes_no_stack_input_and_pushes_silence() {
         let midi = Arc::new(MidiFrameEvents::new());
@@ -450,4 +1813,78 @@ mod tests {
         new.migrate(&mut old);
         assert_eq!(frame(&mut new, &midi, &[]), [2.0, 2.0]);
     }
+
+    #[test]
+    fn decodes_note_on_and_velocity_zero_as_off() {
+        assert_eq!(
+            decode_message(&[0x91, 60, 64]),
+            Some(MidiEvent::note_on(1, 60, 64.0 / 127.0))
+        );
+        assert_eq!(
+            decode_message(&[0x91, 60, 0]).map(|event| event.kind),
+            Some(MidiEventKind::NoteOff)
+        );
+    }
+
+    #[test]
+    fn decodes_control_change() {
+        assert_eq!(
+            decode_message(&[0xb2, 64, 127]),
+            Some(MidiEvent::control_change(2, 64, 1.0))
+        );
+    }
+
+    #[test]
+    fn decodes_pitch_bend_around_centre() {
+        assert_eq!(
+            decode_message(&[0xe0, 0, 64]),
+            Some(MidiEvent::pitch_bend(0, 0.0))
+        );
+        assert_eq!(
+            decode_message(&[0xe0, 0, 0]),
+            Some(MidiEvent::pitch_bend(0, -1.0))
+        );
+        assert_eq!(
+            decode_message(&[0xe0, 0x7f, 0x7f]),
+            Some(MidiEvent::pitch_bend(0, 8191.0 / 8192.0))
+        );
+    }
+
+    #[test]
+    fn decodes_clock_and_transport_messages() {
+        for (status, kind) in [
+            (0xf8, MidiEventKind::Clock),
+            (0xfa, MidiEventKind::Start),
+            (0xfb, MidiEventKind::Continue),
+            (0xfc, MidiEventKind::Stop),
+        ] {
+            assert_eq!(decode_message(&[status]), Some(MidiEvent::realtime(kind)));
+        }
+        assert_eq!(decode_message(&[0xfe]), None);
+    }
+
+    #[test]
+    fn decodes_two_byte_channel_pressure() {
+        assert_eq!(
+            decode_message(&[0xd3, 127]),
+            Some(MidiEvent::channel_pressure(3, 1.0))
+        );
+        assert_eq!(decode_message(&[0xd3]), None);
+    }
+
+    #[test]
+    fn encoding_round_trips_through_decoding() {
+        for event in [
+            MidiEvent::note_on(1, 60, 64.0 / 127.0),
+            MidiEvent::note_off(15, 127),
+            MidiEvent::control_change(2, 74, 1.0),
+            MidiEvent::channel_pressure(3, 0.0),
+            MidiEvent::pitch_bend(0, -1.0),
+            MidiEvent::pitch_bend(0, 0.0),
+            MidiEvent::realtime(MidiEventKind::Start),
+        ] {
+            let (message, len) = encode_message(&event);
+            assert_eq!(decode_message(&message[..len]), Some(event));
+        }
+    }
 }
@@ -0,0 +1,329 @@
+use crate::midi::{
+    AtomicMidiEvent, MAX_MIDI_EVENTS_PER_FRAME, MIDI_CHANNELS, MidiEvent, MidiEventKind,
+};
+use audio_vm::{Op, Sample, Stack};
+use std::sync::{
+    Arc,
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+};
+
+/// One bit per channel and note.
+const NOTE_WORDS: usize = MIDI_CHANNELS * 128 / 64;
+
+/// Fixed-capacity per-audio-frame outgoing MIDI event slice.
+///
+/// `midiout:`/`ccout:` ops append to it while the VM runs a frame, and the
+/// audio callback drains it into the ring feeding the MIDI output worker
+/// right after. Both happen on the audio thread; the atomics only make the
+/// shared `Arc` in the compilation `Context` sound.
+///
+/// It also tracks which notes are sounding, so notes whose `midiout:` op was
+/// edited away or that were playing when the VM paused still get their
+/// note-off instead of hanging on the external synth.
+pub struct MidiOutEvents {
+    len: AtomicUsize,
+    events: [AtomicMidiEvent; MAX_MIDI_EVENTS_PER_FRAME],
+    sounding: [AtomicU64; NOTE_WORDS],
+    /// Note-offs owed outside any op, set from whichever thread drops one.
+    releases: [AtomicU64; NOTE_WORDS],
+}
+
+impl Default for MidiOutEvents {
+    fn default() -> Self {
+        MidiOutEvents {
+            len: AtomicUsize::new(0),
+            events: std::array::from_fn(|_| AtomicMidiEvent::default()),
+            sounding: std::array::from_fn(|_| AtomicU64::new(0)),
+            releases: std::array::from_fn(|_| AtomicU64::new(0)),
+        }
+    }
+}
+
+fn note_bit(channel: u8, note: u8) -> (usize, u64) {
+    let index = usize::from(channel & 0x0f) * 128 + usize::from(note & 0x7f);
+    (index / 64, 1 << (index % 64))
+}
+
+impl MidiOutEvents {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue `event` for the current frame; `false` when the frame is full.
+    pub fn push(&self, event: MidiEvent) -> bool {
+        let len = self.len.load(Ordering::Acquire);
+        if len >= MAX_MIDI_EVENTS_PER_FRAME {
+            return false;
+        }
+        self.events[len].store(event);
+        self.len.store(len + 1, Ordering::Release);
+        let (word, bit) = note_bit(event.channel, event.note);
+        match event.kind {
+            MidiEventKind::NoteOn => self.sounding[word].fetch_or(bit, Ordering::Relaxed),
+            MidiEventKind::NoteOff => self.sounding[word].fetch_and(!bit, Ordering::Relaxed),
+            _ => 0,
+        };
+        true
+    }
+
+    /// Send a note-off for `note` with the next drain, e.g. when the op that
+    /// started it is dropped. Safe from any thread.
+    pub fn release(&self, channel: u8, note: u8) {
+        let (word, bit) = note_bit(channel, note);
+        self.releases[word].fetch_or(bit, Ordering::Relaxed);
+    }
+
+    /// Send note-offs for every sounding note, e.g. when playback pauses.
+    pub fn release_all(&self) {
+        for (sounding, releases) in self.sounding.iter().zip(&self.releases) {
+            releases.fetch_or(sounding.load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+    }
+
+    /// Move pending releases and then the queued events into `out`,
+    /// returning how many there were. Releases that don't fit wait for the
+    /// next drain.
+    pub fn drain(&self, out: &mut [MidiEvent; MAX_MIDI_EVENTS_PER_FRAME]) -> usize {
+        let len = self.len.swap(0, Ordering::AcqRel);
+        let mut count = 0;
+        for (word, releases) in self.releases.iter().enumerate() {
+            let mut pending = releases.load(Ordering::Relaxed);
+            while pending != 0 && count + len < out.len() {
+                let bit = pending & pending.wrapping_neg();
+                pending &= !bit;
+                releases.fetch_and(!bit, Ordering::Relaxed);
+                self.sounding[word].fetch_and(!bit, Ordering::Relaxed);
+                let index = word * 64 + bit.trailing_zeros() as usize;
+                out[count] = MidiEvent::note_off((index / 128) as u8, (index % 128) as u8);
+                count += 1;
+            }
+        }
+        for (out, event) in out[count..].iter_mut().zip(self.events.iter()).take(len) {
+            *out = event.load();
+        }
+        count + len
+    }
+}
+
+/// `midiout:CH` — (note, gate) -> note-on with the gate as velocity on each
+/// rising edge and note-off on each falling edge. Reads the left channel.
+pub struct MidiNoteOut {
+    /// `None` for the forgiving muted op that only consumes its inputs.
+    midi_out: Option<Arc<MidiOutEvents>>,
+    channel: u8,
+    previous_gate: Sample,
+    /// Channel and note of the note-on still waiting for its note-off.
+    sounding: Option<(u8, u8)>,
+}
+
+impl MidiNoteOut {
+    pub fn new(midi_out: Arc<MidiOutEvents>, channel: u8) -> Self {
+        MidiNoteOut {
+            midi_out: Some(midi_out),
+            channel: channel.min(15),
+            previous_gate: 0.0,
+            sounding: None,
+        }
+    }
+
+    pub fn muted() -> Self {
+        MidiNoteOut {
+            midi_out: None,
+            channel: 0,
+            previous_gate: 0.0,
+            sounding: None,
+        }
+    }
+}
+
+impl Op for MidiNoteOut {
+    fn perform(&mut self, stack: &mut Stack) {
+        let gate = stack.pop()[0];
+        let note = stack.pop()[0];
+        let previous_gate = std::mem::replace(&mut self.previous_gate, gate);
+        let Some(midi_out) = &self.midi_out else {
+            return;
+        };
+        let rising = previous_gate <= 0.0 && gate > 0.0;
+        let falling = previous_gate > 0.0 && gate <= 0.0;
+        if (rising || falling)
+            && let Some((channel, note)) = self.sounding.take()
+        {
+            midi_out.push(MidiEvent::note_off(channel, note));
+        }
+        if rising && note.is_finite() {
+            let note = note.round().clamp(0.0, 127.0) as u8;
+            if midi_out.push(MidiEvent::note_on(self.channel, note, gate)) {
+                self.sounding = Some((self.channel, note));
+            }
+        }
+    }
+
+    fn migrate(&mut self, other: &mut dyn Op) {
+        if let Some(other) = other.downcast_mut::<Self>() {
+            self.previous_gate = other.previous_gate;
+            self.sounding = other.sounding.take();
+        }
+    }
+}
+
+/// A note still sounding when the op is edited away is released by the next
+/// drain; migration takes it first when the op survives.
+impl Drop for MidiNoteOut {
+    fn drop(&mut self) {
+        if let (Some(midi_out), Some((channel, note))) = (&self.midi_out, self.sounding) {
+            midi_out.release(channel, note);
+        }
+    }
+}
+
+/// `ccout:N[:CH]` — (value) -> control change N with `value` in `0..1`,
+/// sent when its 7-bit value changes but at most once per millisecond, so
+/// audio-rate modulation can't flood a 31250 baud DIN port.
+pub struct MidiControlOut {
+    midi_out: Option<Arc<MidiOutEvents>>,
+    channel: u8,
+    controller: u8,
+    /// Frames between messages.
+    holdoff: usize,
+    countdown: usize,
+    sent: Option<u8>,
+}
+
+impl MidiControlOut {
+    pub fn new(
+        midi_out: Arc<MidiOutEvents>,
+        controller: u8,
+        channel: u8,
+        sample_rate: u32,
+    ) -> Self {
+        MidiControlOut {
+            midi_out: Some(midi_out),
+            channel: channel.min(15),
+            controller: controller.min(127),
+            holdoff: (sample_rate / 1000) as usize,
+            countdown: 0,
+            sent: None,
+        }
+    }
+
+    pub fn muted() -> Self {
+        MidiControlOut {
+            midi_out: None,
+            channel: 0,
+            controller: 0,
+            holdoff: 0,
+            countdown: 0,
+            sent: None,
+        }
+    }
+}
+
+impl Op for MidiControlOut {
+    fn perform(&mut self, stack: &mut Stack) {
+        let value = stack.pop()[0];
+        self.countdown = self.countdown.saturating_sub(1);
+        let Some(midi_out) = &self.midi_out else {
+            return;
+        };
+        if !value.is_finite() || self.countdown > 0 {
+            return;
+        }
+        let value = (value.clamp(0.0, 1.0) * 127.0).round() as u8;
+        if self.sent != Some(value)
+            && midi_out.push(MidiEvent::control_change(
+                self.channel,
+                self.controller,
+                value as Sample / 127.0,
+            ))
+        {
+            self.sent = Some(value);
+            self.countdown = self.holdoff;
+        }
+    }
+
+    fn migrate(&mut self, other: &mut dyn Op) {
+        if let Some(other) = other.downcast_mut::<Self>() {
+            self.sent = other.sent;
+            self.countdown = other.countdown;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MidiEventKind;
+    use audio_vm::CHANNELS;
+
+    fn drain(midi_out: &MidiOutEvents) -> Vec<MidiEvent> {
+        let mut events = [MidiEvent::note_off(0, 0); MAX_MIDI_EVENTS_PER_FRAME];
+        let len = midi_out.drain(&mut events);
+        events[..len].to_vec()
+    }
+
+    fn note_out(op: &mut MidiNoteOut, note: Sample, gate: Sample) {
+        let mut stack = Stack::new();
+        stack.push(&[note; CHANNELS]);
+        stack.push(&[gate; CHANNELS]);
+        op.perform(&mut stack);
+    }
+
+    #[test]
+    fn note_out_sends_note_on_and_off_on_gate_edges() {
+        let midi_out = Arc::new(MidiOutEvents::new());
+        let mut op = MidiNoteOut::new(Arc::clone(&midi_out), 2);
+        note_out(&mut op, 60.2, 0.5);
+        note_out(&mut op, 62.0, 0.5);
+        assert_eq!(drain(&midi_out), [MidiEvent::note_on(2, 60, 0.5)]);
+        note_out(&mut op, 62.0, 0.0);
+        assert_eq!(drain(&midi_out), [MidiEvent::note_off(2, 60)]);
+        // A migrated op still releases the note its predecessor started.
+        note_out(&mut op, 64.0, 1.0);
+        let mut new = MidiNoteOut::new(Arc::clone(&midi_out), 3);
+        new.migrate(&mut op);
+        note_out(&mut new, 64.0, 0.0);
+        assert_eq!(
+            drain(&midi_out),
+            [MidiEvent::note_on(2, 64, 1.0), MidiEvent::note_off(2, 64)]
+        );
+    }
+
+    #[test]
+    fn dropped_ops_and_pauses_release_sounding_notes() {
+        let midi_out = Arc::new(MidiOutEvents::new());
+        let mut op = MidiNoteOut::new(Arc::clone(&midi_out), 0);
+        note_out(&mut op, 60.0, 1.0);
+        assert_eq!(drain(&midi_out), [MidiEvent::note_on(0, 60, 1.0)]);
+        // Released notes go out before the frame's own events.
+        let mut other = MidiNoteOut::new(Arc::clone(&midi_out), 1);
+        drop(op);
+        note_out(&mut other, 62.0, 1.0);
+        assert_eq!(
+            drain(&midi_out),
+            [MidiEvent::note_off(0, 60), MidiEvent::note_on(1, 62, 1.0)]
+        );
+        assert_eq!(drain(&midi_out), []);
+
+        midi_out.release_all();
+        assert_eq!(drain(&midi_out), [MidiEvent::note_off(1, 62)]);
+        assert_eq!(drain(&midi_out), []);
+    }
+
+    #[test]
+    fn control_out_sends_changes_at_most_once_per_millisecond() {
+        let midi_out = Arc::new(MidiOutEvents::new());
+        let mut op = MidiControlOut::new(Arc::clone(&midi_out), 74, 0, 4000);
+        let mut sent = Vec::new();
+        for value in [0.5, 0.5, 0.6, 0.7, 0.7, 0.7, 0.7] {
+            let mut stack = Stack::new();
+            stack.push(&[value; CHANNELS]);
+            op.perform(&mut stack);
+            sent.extend(drain(&midi_out).iter().map(|event| {
+                assert_eq!(event.kind, MidiEventKind::ControlChange);
+                (event.velocity * 127.0).round() as u8
+            }));
+        }
+        assert_eq!(sent, [64, 89]);
+    }
+}
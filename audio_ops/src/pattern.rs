@@ -5,7 +5,7 @@ use nom::Parser;
 use nom::branch::alt;
 use nom::bytes::complete::take_while1;
 use nom::character::complete::{char, digit1, multispace0};
-use nom::combinator::{all_consuming, map, map_res, opt, value};
+use nom::combinator::{all_consuming, map, map_res, opt, recognize, value};
 use nom::multi::{many0, separated_list1};
 use nom::sequence::{delimited, preceded, terminated, tuple};
 
@@ -13,12 +13,14 @@ use nom::sequence::{delimited, preceded, terminated, tuple};
 enum ParseKind {
     Value,
     Gate,
+    Step,
 }
 
 fn warn_invalid(kind: ParseKind, pattern: &str) {
     match kind {
         ParseKind::Value => log::warn!("Invalid numeric pattern: {}", pattern),
         ParseKind::Gate => log::warn!("Invalid gate pattern: {}", pattern),
+        ParseKind::Step => log::warn!("Invalid step pattern: {}", pattern),
     }
 }
 
@@ -35,6 +37,9 @@ struct Cell<T> {
     start: Sample,
     end: Sample,
     value: T,
+    /// Continuation of the previous cycle's last cell before a delayed first
+    /// cell; never an onset.
+    tied: bool,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -43,10 +48,37 @@ enum PatternElement<T> {
     Group(Vec<PatternElement<T>>),
     Alternate(Vec<Vec<PatternElement<T>>>),
     Random(Vec<Vec<PatternElement<T>>>),
+    Polymetric(Vec<PatternElement<T>>, usize),
+    Degrade(Box<PatternElement<T>>, Sample, T),
+    /// `>R`: start late by R of the cell, stretching the previous cell.
+    Nudge(Box<PatternElement<T>>, Sample),
 }
 
 const RANDOM_PERIOD: usize = 256;
 
+const MAX_CHORD_TONES: usize = 8;
+
+/// One numeric pattern cell: a single value or a `+`-joined chord such as
+/// `60+64+67`. `pat:` reads the first tone; `chord:` reads every lane.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Chord {
+    tones: [Sample; MAX_CHORD_TONES],
+    len: usize,
+}
+
+impl Chord {
+    fn single(value: Sample) -> Self {
+        let mut tones = [0.0; MAX_CHORD_TONES];
+        tones[0] = value;
+        Chord { tones, len: 1 }
+    }
+
+    /// Chords shorter than the lane count repeat their top tone.
+    fn lane(&self, lane: usize) -> Sample {
+        self.tones[lane.min(self.len - 1)]
+    }
+}
+
 fn gcd(mut a: usize, mut b: usize) -> usize {
     while b != 0 {
         let r = a % b;
@@ -78,6 +110,12 @@ fn element_period<T>(element: &PatternElement<T>) -> usize {
             .fold(RANDOM_PERIOD, |period, alternative| {
                 lcm(period, pattern_period(alternative))
             }),
+        PatternElement::Polymetric(elements, steps) => lcm(
+            elements.len() / gcd(elements.len(), *steps),
+            pattern_period(elements),
+        ),
+        PatternElement::Degrade(element, _, _) => lcm(RANDOM_PERIOD, element_period(element)),
+        PatternElement::Nudge(element, _) => element_period(element),
     }
 }
 
@@ -87,12 +125,7 @@ fn pattern_period<T>(elements: &[PatternElement<T>]) -> usize {
         .fold(1, |period, element| lcm(period, element_period(element)))
 }
 
-fn random_choice(
-    cycle: usize,
-    random_index: usize,
-    choices: usize,
-    seed_perturbation: u64,
-) -> usize {
+fn random_hash(cycle: usize, random_index: usize, seed_perturbation: u64) -> u64 {
     let mut x = (cycle as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
         ^ (random_index as u64).wrapping_mul(0xBF58_476D_1CE4_E5B9)
         ^ seed_perturbation;
@@ -101,75 +134,192 @@ fn random_choice(
     x ^= x >> 27;
     x = x.wrapping_mul(0x94D0_49BB_1331_11EB);
     x ^= x >> 31;
-    (x as usize) % choices
+    x
 }
 
+fn random_choice(
+    cycle: usize,
+    random_index: usize,
+    choices: usize,
+    seed_perturbation: u64,
+) -> usize {
+    (random_hash(cycle, random_index, seed_perturbation) as usize) % choices
+}
+
+fn random_drop(
+    cycle: usize,
+    random_index: usize,
+    probability: Sample,
+    seed_perturbation: u64,
+) -> bool {
+    let unit = (random_hash(cycle, random_index, seed_perturbation) >> 11) as Sample
+        / (1u64 << 53) as Sample;
+    unit < probability
+}
+
+/// Flatten one sequence level into `cells`. A cell's start is delayed by its
+/// `>R` nudge, or by `swing` on every second cell when no nudge is given; the
+/// previously flattened cell is stretched over the gap and the delayed cell
+/// keeps its end.
+#[allow(clippy::too_many_arguments)]
 fn flatten_elements<T: Copy>(
     elements: &[PatternElement<T>],
     cycle: usize,
     random_counter: &mut usize,
     start: Sample,
     duration: Sample,
+    swing: Sample,
     cells: &mut Vec<Cell<T>>,
     seed_perturbation: u64,
 ) {
     let step = duration / elements.len() as Sample;
     for (index, element) in elements.iter().enumerate() {
-        let cell_start = start + step * index as Sample;
+        let (element, delay) = match element {
+            PatternElement::Nudge(element, nudge) => (element.as_ref(), *nudge),
+            element if index % 2 == 1 => (element, swing),
+            element => (element, 0.0),
+        };
+        let cell_start = start + step * (index as Sample + delay);
         let cell_end = if index + 1 == elements.len() {
             start + duration
         } else {
-            cell_start + step
+            start + step * (index + 1) as Sample
         };
-        match element {
-            PatternElement::Atom(value) => cells.push(Cell {
-                start: cell_start,
-                end: cell_end,
-                value: *value,
-            }),
-            PatternElement::Group(group) => flatten_elements(
-                group,
+        if delay > 0.0
+            && let Some(previous) = cells.last_mut()
+        {
+            previous.end = cell_start;
+        }
+        flatten_element(
+            element,
+            cycle,
+            random_counter,
+            cell_start,
+            cell_end,
+            cells,
+            seed_perturbation,
+        );
+    }
+}
+
+fn flatten_element<T: Copy>(
+    element: &PatternElement<T>,
+    cycle: usize,
+    random_counter: &mut usize,
+    cell_start: Sample,
+    cell_end: Sample,
+    cells: &mut Vec<Cell<T>>,
+    seed_perturbation: u64,
+) {
+    let duration = cell_end - cell_start;
+    match element {
+        PatternElement::Atom(value) => cells.push(Cell {
+            start: cell_start,
+            end: cell_end,
+            value: *value,
+            tied: false,
+        }),
+        PatternElement::Group(group) => flatten_elements(
+            group,
+            cycle,
+            random_counter,
+            cell_start,
+            duration,
+            0.0,
+            cells,
+            seed_perturbation,
+        ),
+        PatternElement::Alternate(alternatives) => {
+            let alternative = &alternatives[cycle % alternatives.len()];
+            flatten_elements(
+                alternative,
                 cycle,
                 random_counter,
                 cell_start,
-                step,
+                duration,
+                0.0,
                 cells,
                 seed_perturbation,
-            ),
-            PatternElement::Alternate(alternatives) => {
-                let alternative = &alternatives[cycle % alternatives.len()];
-                flatten_elements(
-                    alternative,
+            );
+        }
+        PatternElement::Random(alternatives) => {
+            let random_index = *random_counter;
+            *random_counter += 1;
+            let alternative = &alternatives
+                [random_choice(cycle, random_index, alternatives.len(), seed_perturbation)];
+            flatten_elements(
+                alternative,
+                cycle,
+                random_counter,
+                cell_start,
+                duration,
+                0.0,
+                cells,
+                seed_perturbation,
+            );
+        }
+        PatternElement::Degrade(element, probability, rest) => {
+            let random_index = *random_counter;
+            *random_counter += 1;
+            if random_drop(cycle, random_index, *probability, seed_perturbation) {
+                cells.push(Cell {
+                    start: cell_start,
+                    end: cell_end,
+                    value: *rest,
+                    tied: false,
+                });
+            } else {
+                flatten_element(
+                    element,
                     cycle,
                     random_counter,
                     cell_start,
-                    step,
+                    cell_end,
                     cells,
                     seed_perturbation,
                 );
             }
-            PatternElement::Random(alternatives) => {
-                let random_index = *random_counter;
-                *random_counter += 1;
-                let alternative = &alternatives
-                    [random_choice(cycle, random_index, alternatives.len(), seed_perturbation)];
+        }
+        PatternElement::Polymetric(polymetric, steps) => {
+            let sub_step = duration / *steps as Sample;
+            for index in 0..*steps {
+                let element = &polymetric[(cycle * steps + index) % polymetric.len()];
+                let sub_start = cell_start + sub_step * index as Sample;
+                let sub_duration = if index + 1 == *steps {
+                    cell_end - sub_start
+                } else {
+                    sub_step
+                };
                 flatten_elements(
-                    alternative,
+                    std::slice::from_ref(element),
                     cycle,
                     random_counter,
-                    cell_start,
-                    step,
+                    sub_start,
+                    sub_duration,
+                    0.0,
                     cells,
                     seed_perturbation,
                 );
             }
         }
+        // A nudge below a sequence level (e.g. under `?`) has no neighbour to
+        // borrow time from in this call; `flatten_elements` handles the rest.
+        PatternElement::Nudge(element, _) => flatten_element(
+            element,
+            cycle,
+            random_counter,
+            cell_start,
+            cell_end,
+            cells,
+            seed_perturbation,
+        ),
     }
 }
 
 fn flatten_pattern<T: Copy>(
     elements: &[PatternElement<T>],
     cycle: usize,
+    swing: Sample,
     seed_perturbation: u64,
 ) -> Vec<Cell<T>> {
     let mut cells = Vec::new();
@@ -181,6 +331,7 @@ fn flatten_pattern<T: Copy>(
             &mut random_counter,
             0.0,
             1.0,
+            swing,
             &mut cells,
             seed_perturbation,
         );
@@ -188,6 +339,33 @@ fn flatten_pattern<T: Copy>(
     cells
 }
 
+/// A delayed first cell leaves a gap at the start of the cycle that belongs
+/// to the previous cycle's last cell; fill it with a tied copy so readers
+/// hold that value without seeing a new onset.
+fn tie_leading_gaps<T: Copy>(variants: &mut [Vec<Cell<T>>]) {
+    let lasts = variants
+        .iter()
+        .map(|cells| cells.last().map(|cell| cell.value))
+        .collect::<Vec<_>>();
+    for (cycle, cells) in variants.iter_mut().enumerate() {
+        let previous = lasts[(cycle + lasts.len() - 1) % lasts.len()];
+        if let (Some(first), Some(value)) = (cells.first(), previous)
+            && first.start > 0.0
+        {
+            let end = first.start;
+            cells.insert(
+                0,
+                Cell {
+                    start: 0.0,
+                    end,
+                    value,
+                    tied: true,
+                },
+            );
+        }
+    }
+}
+
 fn cell_index<T>(phase: Sample, cells: &[Cell<T>]) -> usize {
     let phase = wrap_phase(phase);
     cells
@@ -213,12 +391,14 @@ impl<T> Pattern<T> {
 
 fn compile_pattern<T: Copy>(
     elements: Vec<PatternElement<T>>,
+    swing: Sample,
     seed_perturbation: u64,
 ) -> Pattern<T> {
     let period = pattern_period(&elements).max(1);
-    let variants = (0..period)
-        .map(|cycle| flatten_pattern(&elements, cycle, seed_perturbation))
+    let mut variants = (0..period)
+        .map(|cycle| flatten_pattern(&elements, cycle, swing, seed_perturbation))
         .collect::<Vec<_>>();
+    tie_leading_gaps(&mut variants);
     if variants.iter().any(Vec::is_empty) {
         Pattern {
             variants: Vec::new(),
@@ -281,6 +461,78 @@ fn positive_repeat(input: &str) -> IResult<&str, usize> {
     }
 }
 
+fn polymetric_steps(input: &str) -> IResult<&str, usize> {
+    let (input, steps) = preceded(char('%'), unsigned)(input)?;
+    if steps == 0 {
+        Err(nom::Err::Error(nom::error::Error::new(
+            input,
+            nom::error::ErrorKind::Verify,
+        )))
+    } else {
+        Ok((input, steps))
+    }
+}
+
+fn probability(input: &str) -> IResult<&str, Sample> {
+    map_res(
+        recognize(tuple((digit1, opt(tuple((char('.'), digit1)))))),
+        |token: &str| {
+            let probability = token.parse::<Sample>().map_err(|_| ())?;
+            (0.0..=1.0)
+                .contains(&probability)
+                .then_some(probability)
+                .ok_or(())
+        },
+    )(input)
+}
+
+fn degrade_suffix(input: &str) -> IResult<&str, Option<Sample>> {
+    opt(preceded(
+        char('?'),
+        map(opt(probability), |probability| probability.unwrap_or(0.5)),
+    ))(input)
+}
+
+fn degrade<T: Copy>(
+    elements: Vec<PatternElement<T>>,
+    probability: Option<Sample>,
+    rest: T,
+) -> Vec<PatternElement<T>> {
+    match probability {
+        Some(probability) => elements
+            .into_iter()
+            .map(|element| PatternElement::Degrade(Box::new(element), probability, rest))
+            .collect(),
+        None => elements,
+    }
+}
+
+/// `>R` start delay as a fraction of the cell, `0 <= R < 1`.
+fn nudge_suffix(input: &str) -> IResult<&str, Option<Sample>> {
+    opt(preceded(
+        char('>'),
+        map_res(
+            probability,
+            |nudge| {
+                if nudge < 1.0 { Ok(nudge) } else { Err(()) }
+            },
+        ),
+    ))(input)
+}
+
+fn nudge<T: Copy>(
+    elements: Vec<PatternElement<T>>,
+    delay: Option<Sample>,
+) -> Vec<PatternElement<T>> {
+    match delay {
+        Some(delay) => elements
+            .into_iter()
+            .map(|element| PatternElement::Nudge(Box::new(element), delay))
+            .collect(),
+        None => elements,
+    }
+}
+
 fn euclidean_values<T: Copy>(
     pulses: usize,
     steps: usize,
@@ -367,7 +619,7 @@ fn parse_ratio_constant(token: &str) -> Option<Sample> {
     }
 }
 
-fn parse_value_constant(token: &str) -> Result<Sample, ()> {
+pub(crate) fn parse_value_constant(token: &str) -> Result<Sample, ()> {
     let value = token
         .parse::<Sample>()
         .ok()
@@ -377,6 +629,24 @@ fn parse_value_constant(token: &str) -> Result<Sample, ()> {
     value.is_finite().then_some(value).ok_or(())
 }
 
+fn parse_chord_constant(token: &str) -> Result<Chord, ()> {
+    if let Ok(value) = parse_value_constant(token) {
+        return Ok(Chord::single(value));
+    }
+    let mut chord = Chord {
+        tones: [0.0; MAX_CHORD_TONES],
+        len: 0,
+    };
+    for tone in token.split('+') {
+        if chord.len == MAX_CHORD_TONES {
+            return Err(());
+        }
+        chord.tones[chord.len] = parse_value_constant(tone)?;
+        chord.len += 1;
+    }
+    Ok(chord)
+}
+
 fn finite_sample_token(input: &str) -> IResult<&str, Sample> {
     map_res(
         take_while1(|ch: char| ch != ',' && ch != ')' && !ch.is_ascii_whitespace()),
@@ -419,7 +689,7 @@ fn value_euclidean_args(input: &str) -> IResult<&str, (usize, usize, isize, Samp
     )(input)
 }
 
-fn value_atom(input: &str) -> IResult<&str, Vec<PatternElement<Option<Sample>>>> {
+fn value_atom(input: &str) -> IResult<&str, Vec<PatternElement<Option<Chord>>>> {
     alt((
         value(vec![PatternElement::Atom(None)], char('_')),
         map_res(
@@ -435,30 +705,39 @@ fn value_atom(input: &str) -> IResult<&str, Vec<PatternElement<Option<Sample>>>>
                         && ch != '|'
                         && ch != '*'
                         && ch != '('
+                        && ch != '{'
+                        && ch != '}'
+                        && ch != '%'
+                        && ch != '?'
                 }),
                 opt(value_euclidean_args),
             )),
             |(token, euclid): (&str, Option<(usize, usize, isize, Sample)>)| {
-                let value = parse_value_constant(token)?;
+                let chord = parse_chord_constant(token)?;
                 Ok::<_, ()>(match euclid {
-                    Some((pulses, steps, offset, off)) => {
-                        euclidean_values(pulses, steps, offset, Some(value), Some(off)).ok_or(())?
-                    }
-                    None => vec![PatternElement::Atom(Some(value))],
+                    Some((pulses, steps, offset, off)) => euclidean_values(
+                        pulses,
+                        steps,
+                        offset,
+                        Some(chord),
+                        Some(Chord::single(off)),
+                    )
+                    .ok_or(())?,
+                    None => vec![PatternElement::Atom(Some(chord))],
                 })
             },
         ),
     ))(input)
 }
 
-fn value_group(input: &str) -> IResult<&str, Vec<PatternElement<Option<Sample>>>> {
+fn value_group(input: &str) -> IResult<&str, Vec<PatternElement<Option<Chord>>>> {
     map(
         delimited(char('['), value_sequence, char(']')),
         |elements| vec![PatternElement::Group(elements)],
     )(input)
 }
 
-fn value_alternate(input: &str) -> IResult<&str, Vec<PatternElement<Option<Sample>>>> {
+fn value_alternate(input: &str) -> IResult<&str, Vec<PatternElement<Option<Chord>>>> {
     map(
         delimited(
             char('<'),
@@ -469,11 +748,33 @@ fn value_alternate(input: &str) -> IResult<&str, Vec<PatternElement<Option<Sampl
     )(input)
 }
 
-fn value_base(input: &str) -> IResult<&str, Vec<PatternElement<Option<Sample>>>> {
-    ws(alt((value_group, value_alternate, value_atom)))(input)
+fn value_polymetric(input: &str) -> IResult<&str, Vec<PatternElement<Option<Chord>>>> {
+    map(
+        tuple((
+            delimited(char('{'), value_sequence, char('}')),
+            polymetric_steps,
+        )),
+        |(elements, steps)| vec![PatternElement::Polymetric(elements, steps)],
+    )(input)
+}
+
+fn value_base(input: &str) -> IResult<&str, Vec<PatternElement<Option<Chord>>>> {
+    map(
+        ws(tuple((
+            alt((value_group, value_alternate, value_polymetric, value_atom)),
+            nudge_suffix,
+            degrade_suffix,
+        ))),
+        |(elements, delay, probability)| {
+            nudge(
+                degrade(elements, probability, Some(Chord::single(0.0))),
+                delay,
+            )
+        },
+    )(input)
 }
 
-fn value_item(input: &str) -> IResult<&str, Vec<PatternElement<Option<Sample>>>> {
+fn value_item(input: &str) -> IResult<&str, Vec<PatternElement<Option<Chord>>>> {
     let (input, choices) = separated_list1(char('|'), value_base)(input)?;
     let elements = if choices.len() == 1 {
         choices.into_iter().next().unwrap()
@@ -484,13 +785,13 @@ fn value_item(input: &str) -> IResult<&str, Vec<PatternElement<Option<Sample>>>>
     Ok((input, (0..repeat).flat_map(|_| elements.clone()).collect()))
 }
 
-fn value_sequence(input: &str) -> IResult<&str, Vec<PatternElement<Option<Sample>>>> {
+fn value_sequence(input: &str) -> IResult<&str, Vec<PatternElement<Option<Chord>>>> {
     map(separated_list1(char(','), value_item), |items| {
         items.into_iter().flatten().collect()
     })(input)
 }
 
-fn resolve_value_holds(cells: Vec<Cell<Option<Sample>>>) -> Vec<Cell<Sample>> {
+fn resolve_value_holds(cells: Vec<Cell<Option<Chord>>>) -> Vec<Cell<Chord>> {
     let Some(mut held) = cells.iter().rev().find_map(|cell| cell.value) else {
         return Vec::new();
     };
@@ -504,23 +805,39 @@ fn resolve_value_holds(cells: Vec<Cell<Option<Sample>>>) -> Vec<Cell<Sample>> {
                 start: cell.start,
                 end: cell.end,
                 value: held,
+                tied: cell.tied,
             }
         })
         .collect()
 }
 
-fn parse_values(pattern: &str, seed_perturbation: u64) -> Pattern<Sample> {
-    let parsed = all_consuming(terminated(value_sequence, multispace0))(pattern);
+/// Split an optional `:swing:R` suffix off a pattern, `0 <= R < 1`.
+fn split_swing(pattern: &str) -> Option<(&str, Sample)> {
+    match pattern.rsplit_once(":swing:") {
+        Some((pattern, swing)) => {
+            let swing = parse_value_constant(swing).ok()?;
+            (0.0..1.0).contains(&swing).then_some((pattern, swing))
+        }
+        None => Some((pattern, 0.0)),
+    }
+}
+
+fn parse_values(text: &str, seed_perturbation: u64) -> Pattern<Chord> {
+    let parsed = split_swing(text).and_then(|(pattern, swing)| {
+        let (_, elements) = all_consuming(terminated(value_sequence, multispace0))(pattern).ok()?;
+        Some((elements, swing))
+    });
     match parsed {
-        Ok((_, elements)) => {
+        Some((elements, swing)) => {
             let period = pattern_period(&elements).max(1);
-            let variants = (0..period)
+            let mut variants = (0..period)
                 .map(|cycle| {
-                    resolve_value_holds(flatten_pattern(&elements, cycle, seed_perturbation))
+                    resolve_value_holds(flatten_pattern(&elements, cycle, swing, seed_perturbation))
                 })
                 .collect::<Vec<_>>();
+            tie_leading_gaps(&mut variants);
             if variants.iter().any(Vec::is_empty) {
-                warn_invalid(ParseKind::Value, pattern);
+                warn_invalid(ParseKind::Value, text);
                 Pattern {
                     variants: Vec::new(),
                 }
@@ -528,8 +845,8 @@ fn parse_values(pattern: &str, seed_perturbation: u64) -> Pattern<Sample> {
                 Pattern { variants }
             }
         }
-        Err(_) => {
-            warn_invalid(ParseKind::Value, pattern);
+        None => {
+            warn_invalid(ParseKind::Value, text);
             Pattern {
                 variants: Vec::new(),
             }
@@ -575,8 +892,31 @@ fn gate_alternate(input: &str) -> IResult<&str, Vec<PatternElement<bool>>> {
     )(input)
 }
 
+fn gate_polymetric(input: &str) -> IResult<&str, Vec<PatternElement<bool>>> {
+    map_res(
+        tuple((
+            delimited(char('{'), gate_sequence, char('}')),
+            polymetric_steps,
+        )),
+        |(elements, steps)| {
+            if elements.is_empty() {
+                Err(())
+            } else {
+                Ok(vec![PatternElement::Polymetric(elements, steps)])
+            }
+        },
+    )(input)
+}
+
 fn gate_base(input: &str) -> IResult<&str, Vec<PatternElement<bool>>> {
-    ws(alt((gate_group, gate_alternate, gate_atom)))(input)
+    map(
+        ws(tuple((
+            alt((gate_group, gate_alternate, gate_polymetric, gate_atom)),
+            nudge_suffix,
+            degrade_suffix,
+        ))),
+        |(elements, delay, probability)| nudge(degrade(elements, probability, false), delay),
+    )(input)
 }
 
 fn gate_item(input: &str) -> IResult<&str, Vec<PatternElement<bool>>> {
@@ -597,10 +937,14 @@ fn gate_sequence(input: &str) -> IResult<&str, Vec<PatternElement<bool>>> {
     )(input)
 }
 
-fn parse_gates(pattern: &str, seed_perturbation: u64) -> Pattern<bool> {
-    match all_consuming(terminated(gate_sequence, multispace0))(pattern) {
-        Ok((_, elements)) if !elements.is_empty() => {
-            let pattern = compile_pattern(elements, seed_perturbation);
+fn parse_gates(text: &str, seed_perturbation: u64) -> Pattern<bool> {
+    let parsed = split_swing(text).and_then(|(pattern, swing)| {
+        let (_, elements) = all_consuming(terminated(gate_sequence, multispace0))(pattern).ok()?;
+        (!elements.is_empty()).then_some((elements, swing))
+    });
+    match parsed {
+        Some((elements, swing)) => {
+            let pattern = compile_pattern(elements, swing, seed_perturbation);
             if pattern.is_empty() {
                 Pattern {
                     variants: Vec::new(),
@@ -609,8 +953,134 @@ fn parse_gates(pattern: &str, seed_perturbation: u64) -> Pattern<bool> {
                 pattern
             }
         }
-        _ => {
-            warn_invalid(ParseKind::Gate, pattern);
+        None => {
+            warn_invalid(ParseKind::Gate, text);
+            Pattern {
+                variants: Vec::new(),
+            }
+        }
+    }
+}
+
+/// Step record suffix `[:VELOCITY][:rRATCHETS]`, e.g. `x:0.8:r3`.
+fn step_record(input: &str) -> IResult<&str, (Sample, usize)> {
+    map_res(
+        tuple((
+            opt(preceded(char(':'), probability)),
+            opt(preceded(tuple((char(':'), char('r'))), unsigned)),
+        )),
+        |(velocity, ratchets)| match ratchets {
+            Some(0) => Err(()),
+            ratchets => Ok((velocity.unwrap_or(1.0), ratchets.unwrap_or(1))),
+        },
+    )(input)
+}
+
+/// A velocity step; ratchets split its cell into equal retriggered subcells.
+fn step_element(velocity: Sample, ratchets: usize) -> PatternElement<Sample> {
+    if ratchets == 1 {
+        PatternElement::Atom(velocity)
+    } else {
+        PatternElement::Group(vec![PatternElement::Atom(velocity); ratchets])
+    }
+}
+
+fn step_atom(input: &str) -> IResult<&str, Vec<PatternElement<Sample>>> {
+    alt((
+        map(
+            tuple((
+                preceded(alt((char('x'), char('X'))), step_record),
+                opt(euclidean_args),
+            )),
+            |((velocity, ratchets), euclid)| match euclid {
+                Some((pulses, steps, offset)) => {
+                    euclidean_values(pulses, steps, offset, true, false)
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(|element| match element {
+                            PatternElement::Atom(true) => step_element(velocity, ratchets),
+                            _ => PatternElement::Atom(0.0),
+                        })
+                        .collect()
+                }
+                None => vec![step_element(velocity, ratchets)],
+            },
+        ),
+        value(vec![PatternElement::Atom(0.0)], char('.')),
+    ))(input)
+}
+
+fn step_group(input: &str) -> IResult<&str, Vec<PatternElement<Sample>>> {
+    map(delimited(char('['), step_sequence, char(']')), |elements| {
+        vec![PatternElement::Group(elements)]
+    })(input)
+}
+
+fn step_alternate(input: &str) -> IResult<&str, Vec<PatternElement<Sample>>> {
+    map(
+        delimited(
+            char('<'),
+            separated_list1(char(';'), step_sequence),
+            char('>'),
+        ),
+        |alternatives| vec![PatternElement::Alternate(alternatives)],
+    )(input)
+}
+
+fn step_polymetric(input: &str) -> IResult<&str, Vec<PatternElement<Sample>>> {
+    map_res(
+        tuple((
+            delimited(char('{'), step_sequence, char('}')),
+            polymetric_steps,
+        )),
+        |(elements, steps)| {
+            if elements.is_empty() {
+                Err(())
+            } else {
+                Ok(vec![PatternElement::Polymetric(elements, steps)])
+            }
+        },
+    )(input)
+}
+
+fn step_base(input: &str) -> IResult<&str, Vec<PatternElement<Sample>>> {
+    map(
+        ws(tuple((
+            alt((step_group, step_alternate, step_polymetric, step_atom)),
+            nudge_suffix,
+            degrade_suffix,
+        ))),
+        |(elements, delay, probability)| nudge(degrade(elements, probability, 0.0), delay),
+    )(input)
+}
+
+fn step_item(input: &str) -> IResult<&str, Vec<PatternElement<Sample>>> {
+    let (input, choices) = separated_list1(char('|'), step_base)(input)?;
+    let elements = if choices.len() == 1 {
+        choices.into_iter().next().unwrap()
+    } else {
+        vec![PatternElement::Random(choices)]
+    };
+    let (input, repeat) = positive_repeat(input)?;
+    Ok((input, (0..repeat).flat_map(|_| elements.clone()).collect()))
+}
+
+fn step_sequence(input: &str) -> IResult<&str, Vec<PatternElement<Sample>>> {
+    map(
+        many0(alt((step_item, value(Vec::new(), ws(char(',')))))),
+        |items| items.into_iter().flatten().collect(),
+    )(input)
+}
+
+fn parse_steps(text: &str, seed_perturbation: u64) -> Pattern<Sample> {
+    let parsed = split_swing(text).and_then(|(pattern, swing)| {
+        let (_, elements) = all_consuming(terminated(step_sequence, multispace0))(pattern).ok()?;
+        (!elements.is_empty()).then_some((elements, swing))
+    });
+    match parsed {
+        Some((elements, swing)) => compile_pattern(elements, swing, seed_perturbation),
+        None => {
+            warn_invalid(ParseKind::Step, text);
             Pattern {
                 variants: Vec::new(),
             }
@@ -620,7 +1090,7 @@ fn parse_gates(pattern: &str, seed_perturbation: u64) -> Pattern<bool> {
 
 #[derive(Clone)]
 struct ValuePattern {
-    values: Pattern<Sample>,
+    values: Pattern<Chord>,
 }
 
 impl ValuePattern {
@@ -631,16 +1101,31 @@ impl ValuePattern {
     }
 
     fn render(&self, phase: &Frame, cycle_counts: &[usize; CHANNELS]) -> Frame {
+        self.render_lane(phase, cycle_counts, 0)
+    }
+
+    fn render_lane(&self, phase: &Frame, cycle_counts: &[usize; CHANNELS], lane: usize) -> Frame {
         let mut frame = [0.0; CHANNELS];
         if self.values.is_empty() {
             return frame;
         }
         for (channel, (output, &phase)) in frame.iter_mut().zip(phase).enumerate() {
             let cells = self.values.cells(cycle_counts[channel]);
-            *output = cells[cell_index(phase, cells)].value;
+            *output = cells[cell_index(phase, cells)].value.lane(lane);
         }
         frame
     }
+
+    /// Lane count of the largest chord anywhere in the pattern.
+    fn lanes(&self) -> usize {
+        self.values
+            .variants
+            .iter()
+            .flatten()
+            .map(|cell| cell.value.len)
+            .max()
+            .unwrap_or(1)
+    }
 }
 
 #[derive(Clone, PartialEq)]
@@ -709,6 +1194,50 @@ impl Op for Cycle {
     }
 }
 
+/// `cycle` that jumps back to phase 0 on each rising edge of a reset input,
+/// e.g. to restart patterns with an external sequencer.
+pub struct ResetCycle {
+    cycle: Cycle,
+    previous_reset: Frame,
+}
+
+impl ResetCycle {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            cycle: Cycle::new(sample_rate),
+            previous_reset: [0.0; CHANNELS],
+        }
+    }
+}
+
+impl Op for ResetCycle {
+    fn perform(&mut self, stack: &mut Stack) {
+        let reset = stack.pop();
+        let cps = stack.pop();
+        for ((phase, previous), &reset) in self
+            .cycle
+            .phases
+            .iter_mut()
+            .zip(self.previous_reset.iter_mut())
+            .zip(&reset)
+        {
+            if *previous <= 0.0 && reset > 0.0 {
+                *phase = 0.0;
+            }
+            *previous = reset;
+        }
+        let phase = self.cycle.current_then_advance(&cps);
+        stack.push(&phase);
+    }
+
+    fn migrate(&mut self, other: &mut dyn Op) {
+        if let Some(other) = other.downcast_mut::<Self>() {
+            self.cycle.phases = other.cycle.phases;
+            self.previous_reset = other.previous_reset;
+        }
+    }
+}
+
 pub struct PatternValue {
     pattern: ValuePattern,
     previous_phases: [Option<Sample>; CHANNELS],
@@ -737,6 +1266,53 @@ impl Op for PatternValue {
     }
 }
 
+/// `chord:<PATTERN>` — like `pat:` but pushes one frame per chord lane,
+/// lowest lane first, so `chord:[60+64+67],[62+65+69]` leaves three frames.
+/// The lane count is the size of the largest chord in the pattern.
+pub struct PatternChord {
+    pattern: ValuePattern,
+    lanes: usize,
+    previous_phases: [Option<Sample>; CHANNELS],
+    cycle_counts: [usize; CHANNELS],
+}
+
+impl PatternChord {
+    pub fn new(pattern: &str) -> Self {
+        Self::with_seed(pattern, 0)
+    }
+
+    pub fn with_seed(pattern: &str, seed_perturbation: u64) -> Self {
+        let pattern = ValuePattern::with_seed(pattern, seed_perturbation);
+        Self {
+            lanes: pattern.lanes(),
+            pattern,
+            previous_phases: [None; CHANNELS],
+            cycle_counts: [0; CHANNELS],
+        }
+    }
+
+    pub fn lanes(&self) -> usize {
+        self.lanes
+    }
+}
+
+impl Op for PatternChord {
+    fn perform(&mut self, stack: &mut Stack) {
+        let phase = stack.pop();
+        update_cycle_counts(&phase, &mut self.previous_phases, &mut self.cycle_counts);
+        for lane in 0..self.lanes {
+            stack.push(&self.pattern.render_lane(&phase, &self.cycle_counts, lane));
+        }
+    }
+
+    fn migrate(&mut self, other: &mut dyn Op) {
+        if let Some(other) = other.downcast_mut::<Self>() {
+            self.previous_phases = other.previous_phases;
+            self.cycle_counts = other.cycle_counts;
+        }
+    }
+}
+
 pub struct PatternGate {
     pattern: GatePattern,
     previous_phases: [Option<Sample>; CHANNELS],
@@ -800,7 +1376,7 @@ impl PatternTrigger {
             }
             let cells = self.pattern.gates.cells(self.cycle_counts[channel]);
             let index = cell_index(phase, cells);
-            let active = cells[index].value;
+            let active = cells[index].value && !cells[index].tied;
             let entered_active_cell = self.previous_indices[channel] != Some(index) && active;
             *output = if entered_active_cell || (forward_cycle_wrap && active) {
                 1.0
@@ -854,6 +1430,96 @@ impl Op for PatternTrigger {
     }
 }
 
+/// `seq:<PATTERN>` — step sequencer reading `x[:VELOCITY][:rRATCHETS]`
+/// records. Outputs a gate at the step velocity over active spans; a step
+/// that starts right after another active step opens with a one-sample zero,
+/// so rising-edge consumers (`adsr`, `impulse`, `poly`) retrigger and latch
+/// the new velocity.
+pub struct PatternSeq {
+    steps: Pattern<Sample>,
+    previous_indices: [Option<usize>; CHANNELS],
+    previous_phases: [Option<Sample>; CHANNELS],
+    previous_output: Frame,
+    cycle_counts: [usize; CHANNELS],
+}
+
+impl PatternSeq {
+    pub fn new(pattern: &str) -> Self {
+        Self::with_seed(pattern, 0)
+    }
+
+    pub fn with_seed(pattern: &str, seed_perturbation: u64) -> Self {
+        Self {
+            steps: parse_steps(pattern, seed_perturbation),
+            previous_indices: [None; CHANNELS],
+            previous_phases: [None; CHANNELS],
+            previous_output: [0.0; CHANNELS],
+            cycle_counts: [0; CHANNELS],
+        }
+    }
+
+    fn render(&mut self, phase: &Frame) -> Frame {
+        let mut frame = [0.0; CHANNELS];
+        if self.steps.is_empty() {
+            return frame;
+        }
+
+        for (channel, (output, &phase)) in frame.iter_mut().zip(phase).enumerate() {
+            let phase = wrap_phase(phase);
+            let forward_cycle_wrap = self.previous_phases[channel].is_some_and(|prev| prev > phase);
+            if forward_cycle_wrap {
+                self.cycle_counts[channel] = self.cycle_counts[channel].wrapping_add(1);
+            }
+            let cells = self.steps.cells(self.cycle_counts[channel]);
+            let index = cell_index(phase, cells);
+            let velocity = cells[index].value;
+            let onset = (self.previous_indices[channel] != Some(index) || forward_cycle_wrap)
+                && !cells[index].tied;
+            *output = if velocity > 0.0 && !(onset && self.previous_output[channel] > 0.0) {
+                velocity
+            } else {
+                0.0
+            };
+            self.previous_indices[channel] = Some(index);
+            self.previous_phases[channel] = Some(phase);
+        }
+        self.previous_output = frame;
+
+        frame
+    }
+
+    fn migrate_from(&mut self, other: &Self) {
+        self.previous_phases = other.previous_phases;
+        self.previous_output = other.previous_output;
+        self.cycle_counts = other.cycle_counts;
+
+        if self.steps == other.steps {
+            self.previous_indices = other.previous_indices;
+        } else if !self.steps.is_empty() {
+            for channel in 0..CHANNELS {
+                self.previous_indices[channel] = self.previous_phases[channel].map(|phase| {
+                    let cells = self.steps.cells(self.cycle_counts[channel]);
+                    cell_index(phase, cells)
+                });
+            }
+        }
+    }
+}
+
+impl Op for PatternSeq {
+    fn perform(&mut self, stack: &mut Stack) {
+        let phase = stack.pop();
+        let frame = self.render(&phase);
+        stack.push(&frame);
+    }
+
+    fn migrate(&mut self, other: &mut dyn Op) {
+        if let Some(other) = other.downcast_mut::<Self>() {
+            self.migrate_from(other);
+        }
+    }
+}
+
 pub struct ClockedPatternValue {
     cycle: Cycle,
     pattern: ValuePattern,
@@ -966,6 +1632,40 @@ impl Op for ClockedPatternTrigger {
     }
 }
 
+pub struct ClockedPatternSeq {
+    cycle: Cycle,
+    seq: PatternSeq,
+}
+
+impl ClockedPatternSeq {
+    pub fn new(sample_rate: u32, pattern: &str) -> Self {
+        Self::with_seed(sample_rate, pattern, 0)
+    }
+
+    pub fn with_seed(sample_rate: u32, pattern: &str, seed_perturbation: u64) -> Self {
+        Self {
+            cycle: Cycle::new(sample_rate),
+            seq: PatternSeq::with_seed(pattern, seed_perturbation),
+        }
+    }
+}
+
+impl Op for ClockedPatternSeq {
+    fn perform(&mut self, stack: &mut Stack) {
+        let cps = stack.pop();
+        let phase = self.cycle.current_then_advance(&cps);
+        let frame = self.seq.render(&phase);
+        stack.push(&frame);
+    }
+
+    fn migrate(&mut self, other: &mut dyn Op) {
+        if let Some(other) = other.downcast_mut::<Self>() {
+            self.cycle.phases = other.cycle.phases;
+            self.seq.migrate_from(&other.seq);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -986,6 +1686,23 @@ mod tests {
         assert_eq!(perform(&mut cycle, [2.0, -2.0]), [0.5, 0.5]);
     }
 
+    #[test]
+    fn reset_cycle_restarts_on_rising_edges() {
+        let mut cycle = ResetCycle::new(4);
+        let mut step = |reset: Sample| {
+            let mut stack = Stack::new();
+            stack.push(&[1.0; CHANNELS]);
+            stack.push(&[reset; CHANNELS]);
+            cycle.perform(&mut stack);
+            stack.pop()[0]
+        };
+        assert_eq!(step(0.0), 0.0);
+        assert_eq!(step(0.0), 0.25);
+        assert_eq!(step(1.0), 0.0);
+        // A held reset doesn't stop the cycle.
+        assert_eq!(step(1.0), 0.25);
+    }
+
     #[test]
     fn value_pattern_selects_by_wrapped_phase_per_channel() {
         let mut pat = PatternValue::new("60, 64,67,72");
@@ -1135,6 +1852,77 @@ mod tests {
         assert!(seen.contains(&64.0));
     }
 
+    #[test]
+    fn value_pattern_polymetric_steps_wrap_across_cycles() {
+        let mut pat = PatternValue::new("{60,64,67}%4");
+        let mut seen = Vec::new();
+        for _ in 0..3 {
+            for phase in [0.0, 0.25, 0.5, 0.75] {
+                seen.push(perform(&mut pat, [phase, phase])[0]);
+            }
+        }
+        assert_eq!(
+            seen,
+            [
+                60.0, 64.0, 67.0, 60.0, 64.0, 67.0, 60.0, 64.0, 67.0, 60.0, 64.0, 67.0
+            ]
+        );
+    }
+
+    #[test]
+    fn value_pattern_polymetric_braces_subdivide_their_cell() {
+        let mut pat = PatternValue::new("48,{60,[62,64]}%2");
+        assert_eq!(perform(&mut pat, [0.0, 0.5]), [48.0, 60.0]);
+        assert_eq!(perform(&mut pat, [0.75, 0.9]), [62.0, 64.0]);
+        assert_eq!(perform(&mut pat, [0.0, 0.5]), [48.0, 60.0]);
+    }
+
+    #[test]
+    fn value_pattern_degraded_steps_become_zero_rests() {
+        let mut pat = PatternValue::new("60?1,64?0");
+        assert_eq!(perform(&mut pat, [0.0, 0.5]), [0.0, 64.0]);
+
+        let mut pat = PatternValue::new("60?");
+        let mut seen = Vec::new();
+        for _ in 0..32 {
+            seen.push(perform(&mut pat, [0.0, 0.0])[0]);
+            perform(&mut pat, [0.9, 0.9]);
+        }
+        assert!(seen.contains(&0.0));
+        assert!(seen.contains(&60.0));
+    }
+
+    #[test]
+    fn value_pattern_reads_first_tone_of_chords() {
+        let mut pat = PatternValue::new("[60+64+67],C4+E4");
+        assert_eq!(perform(&mut pat, [0.0, 0.5]), [60.0, 60.0]);
+    }
+
+    #[test]
+    fn chord_pattern_pushes_one_frame_per_lane() {
+        let mut chord = PatternChord::new("[60+64+67],62+65,_,1e+3");
+        assert_eq!(chord.lanes(), 3);
+        let mut stack = Stack::new();
+        for (phase, expected) in [
+            (0.0, [60.0, 64.0, 67.0]),
+            (0.25, [62.0, 65.0, 65.0]),
+            (0.5, [62.0, 65.0, 65.0]),
+            (0.75, [1000.0, 1000.0, 1000.0]),
+        ] {
+            stack.push(&[phase; CHANNELS]);
+            chord.perform(&mut stack);
+            let lanes = [stack.pop()[0], stack.pop()[0], stack.pop()[0]];
+            assert_eq!(lanes, [expected[2], expected[1], expected[0]]);
+        }
+    }
+
+    #[test]
+    fn invalid_chord_pattern_pushes_one_zero_lane() {
+        let mut chord = PatternChord::new("60+,64");
+        assert_eq!(chord.lanes(), 1);
+        assert_eq!(perform(&mut chord, [0.0, 0.0]), [0.0, 0.0]);
+    }
+
     #[test]
     fn invalid_value_patterns_output_zero() {
         for pattern in [
@@ -1154,6 +1942,14 @@ mod tests {
             "60(3,8,nan)",
             "<60,64;>",
             "60|",
+            "{60,64}",
+            "{60,64}%0",
+            "{}%4",
+            "60?1.5",
+            "60??",
+            "60+",
+            "60++64",
+            "1+2+3+4+5+6+7+8+9",
         ] {
             let mut pat = PatternValue::new(pattern);
             assert_eq!(perform(&mut pat, [0.5, 0.5]), [0.0, 0.0]);
@@ -1250,10 +2046,59 @@ mod tests {
         assert_eq!(perform(&mut gate, [0.667, 0.9999]), [0.0, 0.0]);
     }
 
+    #[test]
+    fn gate_pattern_polymetric_steps_wrap_across_cycles() {
+        let mut gate = PatternGate::new("{x..}%4");
+        let mut seen = Vec::new();
+        for _ in 0..3 {
+            for phase in [0.0, 0.25, 0.5, 0.75] {
+                seen.push(perform(&mut gate, [phase, phase])[0]);
+            }
+        }
+        assert_eq!(
+            seen,
+            [1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn gate_pattern_degrade_drops_steps_with_given_probability() {
+        let mut gate = PatternGate::new("x?1x?0");
+        assert_eq!(perform(&mut gate, [0.0, 0.5]), [0.0, 1.0]);
+
+        let mut gate = PatternGate::new("x?0.3*8");
+        let mut active = 0;
+        for _ in 0..RANDOM_PERIOD {
+            for step in 0..8 {
+                let phase = step as Sample / 8.0;
+                active += perform(&mut gate, [phase, phase])[0] as usize;
+            }
+        }
+        let ratio = active as Sample / (RANDOM_PERIOD * 8) as Sample;
+        assert!((ratio - 0.7).abs() < 0.05, "{ratio}");
+    }
+
+    #[test]
+    fn gate_pattern_degrade_is_reproducible_per_seed() {
+        let render = |seed| {
+            let mut gate = PatternGate::with_seed("x?x?.x?", seed);
+            let mut frames = Vec::new();
+            for _ in 0..16 {
+                for phase in [0.0, 0.25, 0.5, 0.75] {
+                    frames.push(perform(&mut gate, [phase, phase])[0]);
+                }
+            }
+            frames
+        };
+        assert_eq!(render(7), render(7));
+        assert_ne!(render(7), render(8));
+    }
+
     #[test]
     fn invalid_gate_patterns_output_zero() {
         for pattern in [
-            "", "x..q", "1..0", "x[.", "x*", "x*0", "x|", "e(5,4)", "e(3,0)", "e(3,)",
+            "", "x..q", "1..0", "x[.", "x*", "x*0", "x|", "e(5,4)", "e(3,0)", "e(3,)", "{x.}",
+            "{x.}%0", "{}%4", "x?2", "?",
         ] {
             let mut gate = PatternGate::new(pattern);
             assert_eq!(perform(&mut gate, [0.0, 0.5]), [0.0, 0.0]);
@@ -1324,4 +2169,156 @@ mod tests {
             assert_eq!(clocked_frame, explicit_frame);
         }
     }
+
+    fn seq_channel(seq: &mut PatternSeq, phases: &[Sample]) -> Vec<Sample> {
+        phases
+            .iter()
+            .map(|&phase| perform(seq, [phase, phase])[0])
+            .collect()
+    }
+
+    #[test]
+    fn seq_outputs_step_velocities_and_dips_between_adjacent_steps() {
+        let mut seq = PatternSeq::new("x:0.8,x:0.5,.,x");
+        assert_eq!(
+            seq_channel(&mut seq, &[0.0, 0.1, 0.25, 0.3, 0.5, 0.75, 0.8, 0.0, 0.1]),
+            vec![0.8, 0.8, 0.0, 0.5, 0.0, 1.0, 1.0, 0.0, 0.8]
+        );
+    }
+
+    #[test]
+    fn seq_ratchets_retrigger_inside_one_step() {
+        let mut seq = PatternSeq::new("x:0.6:r3.");
+        let phases = (0..12).map(|i| i as Sample / 24.0).collect::<Vec<_>>();
+        assert_eq!(
+            seq_channel(&mut seq, &phases),
+            vec![0.6, 0.6, 0.6, 0.6, 0.0, 0.6, 0.6, 0.6, 0.0, 0.6, 0.6, 0.6]
+        );
+        let mut ratchet_only = PatternSeq::new("x:r2");
+        assert_eq!(
+            seq_channel(&mut ratchet_only, &[0.0, 0.25, 0.5, 0.75]),
+            vec![1.0, 1.0, 0.0, 1.0]
+        );
+    }
+
+    #[test]
+    fn seq_reuses_pattern_structure_and_degrade() {
+        let mut seq = PatternSeq::new("x:0.5(3,8)");
+        let phases = (0..8).map(|i| i as Sample / 8.0).collect::<Vec<_>>();
+        assert_eq!(
+            seq_channel(&mut seq, &phases),
+            vec![0.5, 0.0, 0.0, 0.5, 0.0, 0.0, 0.5, 0.0]
+        );
+        let mut always = PatternSeq::new("[x:0.7?0,x?1]");
+        assert_eq!(seq_channel(&mut always, &[0.0, 0.5]), vec![0.7, 0.0]);
+        // Each cycle draws the step afresh from the seed.
+        let mut degraded = PatternSeq::with_seed("x?0.5", 1);
+        let cycles = (0..8)
+            .map(|_| seq_channel(&mut degraded, &[0.0, 0.9])[0])
+            .collect::<Vec<_>>();
+        assert_eq!(cycles, vec![0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn seq_migration_preserves_step_state() {
+        let mut previous = PatternSeq::new("x:0.8,x");
+        assert_eq!(perform(&mut previous, [0.0, 0.0]), [0.8, 0.8]);
+
+        let mut next = PatternSeq::new("x:0.8,x");
+        next.migrate(&mut previous);
+        assert_eq!(perform(&mut next, [0.1, 0.5]), [0.8, 0.0]);
+
+        let mut clocked = ClockedPatternSeq::new(4, "x:0.5,x");
+        assert_eq!(perform(&mut clocked, [2.0, 2.0]), [0.5, 0.5]);
+        assert_eq!(perform(&mut clocked, [2.0, 2.0]), [0.0, 0.0]);
+        assert_eq!(perform(&mut clocked, [2.0, 2.0]), [0.5, 0.5]);
+    }
+
+    #[test]
+    fn invalid_step_patterns_output_zero() {
+        for pattern in [
+            "",
+            "x:",
+            "x:2",
+            "x:0.5:",
+            "x:r0",
+            "x:r",
+            "x:0.5:r2:0.1",
+            "60",
+            "x[.",
+            "{x.}",
+            "x?2",
+        ] {
+            let mut seq = PatternSeq::new(pattern);
+            assert_eq!(perform(&mut seq, [0.0, 0.5]), [0.0, 0.0], "{pattern}");
+        }
+    }
+
+    #[test]
+    fn swing_delays_every_second_top_level_cell() {
+        let mut pat = PatternValue::new("1,2,[3,5],4:swing:0.5");
+        for (phase, expected) in [
+            (0.3, 1.0),
+            (0.4, 2.0),
+            (0.6, 3.0),
+            (0.7, 5.0),
+            (0.8, 5.0),
+            (0.9, 4.0),
+        ] {
+            assert_eq!(perform(&mut pat, [phase, phase])[0], expected, "{phase}");
+        }
+        let mut gate = PatternGate::new("x.x.:swing:1/3");
+        assert_eq!(perform(&mut gate, [0.3, 0.75]), [1.0, 1.0]);
+    }
+
+    #[test]
+    fn nudge_starts_a_step_late_and_stretches_the_previous_one() {
+        let mut trig = PatternTrigger::new("x,x>0.5");
+        assert_eq!(perform(&mut trig, [0.0, 0.0]), [1.0, 1.0]);
+        assert_eq!(perform(&mut trig, [0.6, 0.6]), [0.0, 0.0]);
+        assert_eq!(perform(&mut trig, [0.8, 0.8]), [1.0, 1.0]);
+
+        let mut gate = PatternGate::new("x.>0.5?0");
+        assert_eq!(perform(&mut gate, [0.6, 0.8]), [1.0, 0.0]);
+    }
+
+    #[test]
+    fn nudged_first_step_ties_over_from_the_previous_cycle() {
+        let mut trig = PatternTrigger::new("x>0.5");
+        assert_eq!(perform(&mut trig, [0.0, 0.0]), [0.0, 0.0]);
+        assert_eq!(perform(&mut trig, [0.5, 0.5]), [1.0, 1.0]);
+        assert_eq!(perform(&mut trig, [0.9, 0.9]), [0.0, 0.0]);
+        assert_eq!(perform(&mut trig, [0.1, 0.1]), [0.0, 0.0]);
+        assert_eq!(perform(&mut trig, [0.6, 0.6]), [1.0, 1.0]);
+
+        let mut pat = PatternValue::new("<60;64>>0.5");
+        assert_eq!(perform(&mut pat, [0.75, 0.75]), [60.0, 60.0]);
+        assert_eq!(perform(&mut pat, [0.25, 0.25]), [60.0, 60.0]);
+        assert_eq!(perform(&mut pat, [0.75, 0.75]), [64.0, 64.0]);
+        assert_eq!(perform(&mut pat, [0.25, 0.25]), [64.0, 64.0]);
+        assert_eq!(perform(&mut pat, [0.75, 0.75]), [60.0, 60.0]);
+
+        let mut seq = PatternSeq::new("x:0.5,x>0.5");
+        assert_eq!(
+            seq_channel(&mut seq, &[0.0, 0.5, 0.75, 0.8, 0.0]),
+            vec![0.5, 0.5, 0.0, 1.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn invalid_swing_and_nudge_output_zero() {
+        for pattern in [
+            "x.:swing:1",
+            "x.:swing:",
+            "x.:swing:-0.1",
+            "x>1",
+            "x>",
+            "x>0.5>0.1",
+        ] {
+            let mut gate = PatternGate::new(pattern);
+            assert_eq!(perform(&mut gate, [0.0, 0.5]), [0.0, 0.0], "{pattern}");
+        }
+        let mut pat = PatternValue::new("60,64:swing:x");
+        assert_eq!(perform(&mut pat, [0.0, 0.5]), [0.0, 0.0]);
+    }
 }
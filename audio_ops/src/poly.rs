@@ -14,52 +14,168 @@
 //! amplitude passes through, so gates can carry velocity), and all other
 //! voices receive 0. Each voice's sub-program runs against a sub-stack
 //! initialized to `[latched_value, routed_ctl]`.
-use audio_vm::{CHANNELS, Frame, Op, Stack, Statement, migrate_program_state};
+//!
+//! `<lane_1> .. <lane_K> <ctl> poly:N:K` consumes K value lanes instead of
+//! one (e.g. the output of `chord:`) and allocates one voice per lane on each
+//! rising edge, so a chord cell starts K voices at once. Lanes repeating an
+//! earlier lane's value in the same edge don't allocate another voice, and
+//! every voice allocated on the latest edge receives the live control signal.
+//!
+//! `<v1> .. <vK> <ctl> poly:N,K` latches K values into each allocated voice
+//! (e.g. note, velocity, cutoff and pan) and seeds its sub-stack with
+//! `[v1 .. vK, routed_ctl]`. With lanes, `poly:N,K:L` reads L groups of K
+//! values, one group per voice.
+//!
+//! `poly:N:idle` adds idle detection: a voice whose control input is down
+//! and whose output stays below `IDLE_THRESHOLD` for the hold time is free.
+//! Free voices are allocated before sounding ones are stolen, and their bodies
+//! are skipped entirely, so CPU follows the number of sounding notes.
+//!
+//! `polyx:N` takes the same arguments but pushes N frames, one per voice in
+//! voice order (voice 0 deepest), for per-voice processing outside the body.
+//!
+//! `<input> [ body ] uni:N[:WIDTH]` is the unison sibling: N copies of the
+//! body run continuously on the same input, each starting from
+//! `[input, spread]` with spread evenly placed over `-1..1`, and their outputs
+//! are summed. With WIDTH each copy is folded to mono and equal-power panned
+//! to `spread * WIDTH` first.
+use crate::pure;
+use audio_vm::{CHANNELS, Frame, Op, Sample, Stack, Statement, migrate_program_state};
 
 const SILENCE: Frame = [0.0; CHANNELS];
 
+/// Peak output below which a released voice counts as quiet (-80 dB).
+const IDLE_THRESHOLD: Sample = 1e-4;
+
+/// How long a released voice must stay quiet before `poly:N:idle` frees it.
+pub const IDLE_HOLD_SECONDS: Sample = 0.05;
+
 struct Voice {
     program: Box<[Statement]>,
-    latched: Frame,
+    /// One frame per latched value, pushed below the control signal.
+    latched: Vec<Frame>,
+    /// Allocated on the latest edge; receives the live control signal.
+    routed: bool,
+    /// Consecutive released samples below `IDLE_THRESHOLD`; never-used voices
+    /// start saturated.
+    quiet: usize,
 }
 
 pub struct Poly {
     voices: Vec<Voice>,
-    /// Most recently allocated voice; receives the live control signal.
+    /// Most recently allocated voice; the next allocation follows it.
     current: Option<usize>,
     /// Previous control frame for rising-edge detection.
     previous_ctl: Frame,
+    /// Latched values per voice.
+    values: usize,
+    /// Value lanes consumed below the control signal, lowest lane first;
+    /// each lane is `values` consecutive frames.
+    lanes: Vec<Frame>,
+    /// Quiet samples after which a voice is free and skipped; `None` runs
+    /// every voice and allocates round-robin.
+    idle_hold: Option<usize>,
+    /// Push this many frames, one per voice in voice order (silence past the
+    /// last voice), instead of their sum.
+    separate_outputs: Option<usize>,
     /// Reused sub-stack for voice bodies.
     stack: Stack,
 }
 
 impl Poly {
     pub fn new(bodies: Vec<Box<[Statement]>>) -> Self {
+        Poly::with_lanes(bodies, 1)
+    }
+
+    pub fn with_lanes(bodies: Vec<Box<[Statement]>>, lanes: usize) -> Self {
+        Poly::with_inputs(bodies, 1, lanes)
+    }
+
+    /// `values` latched frames per voice, `lanes` voices started per edge.
+    pub fn with_inputs(bodies: Vec<Box<[Statement]>>, values: usize, lanes: usize) -> Self {
+        let values = values.max(1);
         Poly {
             voices: bodies
                 .into_iter()
                 .map(|program| Voice {
                     program,
-                    latched: SILENCE,
+                    latched: vec![SILENCE; values],
+                    routed: false,
+                    quiet: usize::MAX,
                 })
                 .collect(),
             current: None,
             previous_ctl: SILENCE,
+            values,
+            lanes: vec![SILENCE; values * lanes.max(1)],
+            idle_hold: None,
+            separate_outputs: None,
             stack: Stack::new(),
         }
     }
 
+    /// Enable idle detection with the given hold time in samples.
+    pub fn set_idle_hold(&mut self, hold: Option<usize>) {
+        self.idle_hold = hold;
+    }
+
+    /// `polyx:N`: push `frames` frames instead of the sum; a zero-voice op
+    /// still pushes them as silence to keep the stack shape.
+    pub fn set_separate_outputs(&mut self, frames: Option<usize>) {
+        self.separate_outputs = frames;
+    }
+
+    fn is_idle(&self, voice: &Voice) -> bool {
+        self.idle_hold.is_some_and(|hold| voice.quiet >= hold)
+    }
+
     /// Forgiving zero-voice op for invalid quotations/arguments: preserves
     /// stack shape (consumes value and ctl, pushes silence).
     pub fn empty() -> Self {
         Poly::new(Vec::new())
     }
+
+    /// Zero-voice op that still consumes `values * lanes` frames and ctl.
+    pub fn empty_with_inputs(values: usize, lanes: usize) -> Self {
+        Poly::with_inputs(Vec::new(), values, lanes)
+    }
+
+    fn allocate(&mut self) {
+        for voice in &mut self.voices {
+            voice.routed = false;
+        }
+        for (lane, values) in self.lanes.chunks(self.values).enumerate() {
+            if self
+                .lanes
+                .chunks(self.values)
+                .take(lane)
+                .any(|x| x == values)
+            {
+                continue;
+            }
+            let len = self.voices.len();
+            let next = self.current.map_or(0, |current| (current + 1) % len);
+            // Prefer the first free voice after the current one; otherwise
+            // steal round-robin.
+            let next = (0..len)
+                .map(|offset| (next + offset) % len)
+                .find(|&voice| self.is_idle(&self.voices[voice]))
+                .unwrap_or(next);
+            let voice = &mut self.voices[next];
+            voice.latched.copy_from_slice(values);
+            voice.routed = true;
+            voice.quiet = 0;
+            self.current = Some(next);
+        }
+    }
 }
 
 impl Op for Poly {
     fn perform(&mut self, stack: &mut Stack) {
         let ctl = stack.pop();
-        let value = stack.pop();
+        for lane in self.lanes.iter_mut().rev() {
+            *lane = stack.pop();
+        }
         let rising = self
             .previous_ctl
             .iter()
@@ -67,30 +183,48 @@ impl Op for Poly {
             .any(|(&previous, &current)| previous <= 0.0 && current > 0.0);
         self.previous_ctl = ctl;
         if rising && !self.voices.is_empty() {
-            let next = self
-                .current
-                .map_or(0, |current| (current + 1) % self.voices.len());
-            self.voices[next].latched = value;
-            self.current = Some(next);
+            self.allocate();
         }
         let mut sum = SILENCE;
-        for (index, voice) in self.voices.iter_mut().enumerate() {
+        let idle_hold = self.idle_hold;
+        for voice in &mut self.voices {
+            if idle_hold.is_some_and(|hold| voice.quiet >= hold) {
+                if self.separate_outputs.is_some() {
+                    stack.push(&SILENCE);
+                }
+                continue;
+            }
             self.stack.reset();
-            self.stack.push(&voice.latched);
-            self.stack.push(if Some(index) == self.current {
-                &ctl
-            } else {
-                &SILENCE
-            });
+            for value in &voice.latched {
+                self.stack.push(value);
+            }
+            self.stack.push(if voice.routed { &ctl } else { &SILENCE });
             for stmt in voice.program.iter_mut() {
                 stmt.op.perform(&mut self.stack);
             }
             let frame = self.stack.peek();
-            for (sum, x) in sum.iter_mut().zip(&frame) {
-                *sum += x;
+            if self.separate_outputs.is_some() {
+                stack.push(&frame);
+            } else {
+                for (sum, x) in sum.iter_mut().zip(&frame) {
+                    *sum += x;
+                }
             }
+            let held = voice.routed && ctl.iter().any(|&x| x > 0.0);
+            voice.quiet = if held || frame.iter().any(|x| x.abs() >= IDLE_THRESHOLD) {
+                0
+            } else {
+                voice.quiet.saturating_add(1)
+            };
+        }
+        match self.separate_outputs {
+            Some(frames) => {
+                for _ in self.voices.len()..frames {
+                    stack.push(&SILENCE);
+                }
+            }
+            None => stack.push(&sum),
         }
-        stack.push(&sum);
     }
 
     fn migrate(&mut self, other: &mut dyn Op) {
@@ -100,17 +234,88 @@ impl Op for Poly {
                 self.current = other.current.map(|current| current % self.voices.len());
             }
             for (voice, other_voice) in self.voices.iter_mut().zip(other.voices.iter_mut()) {
-                voice.latched = other_voice.latched;
+                for (value, &other_value) in voice.latched.iter_mut().zip(&other_voice.latched) {
+                    *value = other_value;
+                }
+                voice.routed = other_voice.routed;
+                voice.quiet = other_voice.quiet;
                 migrate_program_state(&mut voice.program, &mut other_voice.program);
             }
         }
     }
 }
 
+pub struct Unison {
+    copies: Vec<Box<[Statement]>>,
+    /// Stereo spread of the copies, `0..1`; `None` sums them unpanned.
+    width: Option<Sample>,
+    /// Reused sub-stack for copy bodies.
+    stack: Stack,
+}
+
+impl Unison {
+    pub fn new(bodies: Vec<Box<[Statement]>>, width: Option<Sample>) -> Self {
+        Unison {
+            copies: bodies,
+            width,
+            stack: Stack::new(),
+        }
+    }
+
+    /// Forgiving zero-copy op: consumes the input and pushes silence.
+    pub fn empty() -> Self {
+        Unison::new(Vec::new(), None)
+    }
+}
+
+impl Op for Unison {
+    fn perform(&mut self, stack: &mut Stack) {
+        let input = stack.pop();
+        let copies = self.copies.len();
+        let last = copies.saturating_sub(1).max(1) as Sample;
+        let mut sum = SILENCE;
+        for (index, copy) in self.copies.iter_mut().enumerate() {
+            let spread = if copies > 1 {
+                2.0 * index as Sample / last - 1.0
+            } else {
+                0.0
+            };
+            self.stack.reset();
+            self.stack.push(&input);
+            self.stack.push(&[spread; CHANNELS]);
+            for stmt in copy.iter_mut() {
+                stmt.op.perform(&mut self.stack);
+            }
+            let mut frame = self.stack.peek();
+            if let Some(width) = self.width {
+                let mono = (frame[0] + frame[1]) * 0.5;
+                let (l, r) = pure::equal_power_pan(mono, spread * width);
+                frame = [l, r];
+            }
+            for (sum, x) in sum.iter_mut().zip(&frame) {
+                *sum += x;
+            }
+        }
+        stack.push(&sum);
+    }
+
+    /// Pairs state by (copy index, node id), like `Poly`.
+    fn migrate(&mut self, other: &mut dyn Op) {
+        if let Some(other) = other.downcast_mut::<Self>() {
+            for (copy, other_copy) in self.copies.iter_mut().zip(other.copies.iter_mut()) {
+                migrate_program_state(copy, other_copy);
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use audio_vm::Sample;
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
 
     /// Pops ctl and value, pushes `latched_value + ctl * 10` so tests can
     /// observe both routing and latching per voice.
@@ -202,6 +407,232 @@ mod tests {
         assert_eq!(frame(&mut poly, 61.0, 2.0), [80.0, 80.0]);
     }
 
+    #[test]
+    fn lanes_allocate_one_voice_per_distinct_value_on_each_edge() {
+        let mut poly = Poly::with_lanes(
+            (0..4)
+                .map(|_| {
+                    vec![Statement {
+                        id: 1,
+                        op: Box::new(Probe) as Box<dyn Op>,
+                    }]
+                    .into_boxed_slice()
+                })
+                .collect(),
+            3,
+        );
+        let mut chord = |lanes: [Sample; 3], ctl: Sample| {
+            let mut stack = Stack::new();
+            for lane in lanes {
+                stack.push(&[lane; CHANNELS]);
+            }
+            stack.push(&[ctl; CHANNELS]);
+            poly.perform(&mut stack);
+            stack.peek()[0]
+        };
+        // Three voices latch the chord and all receive the live ctl.
+        assert_eq!(chord([60.0, 64.0, 67.0], 1.0), 191.0 + 30.0);
+        assert_eq!(chord([0.0, 0.0, 0.0], 0.0), 191.0);
+        // A two-tone chord padded to three lanes allocates two voices:
+        // voice 3 takes 62, voice 0 (oldest) is stolen for 65.
+        assert_eq!(
+            chord([62.0, 65.0, 65.0], 1.0),
+            64.0 + 67.0 + 62.0 + 65.0 + 20.0
+        );
+    }
+
+    /// Pops ctl, velocity and note; pushes `note + velocity * 100 + ctl * 10`.
+    struct NoteProbe;
+
+    impl Op for NoteProbe {
+        fn perform(&mut self, stack: &mut Stack) {
+            let ctl = stack.pop();
+            let velocity = stack.pop();
+            let note = stack.pop();
+            stack.push(&[note[0] + velocity[0] * 100.0 + ctl[0] * 10.0; CHANNELS]);
+        }
+    }
+
+    fn note_poly(voices: usize) -> Poly {
+        Poly::with_inputs(
+            (0..voices)
+                .map(|_| {
+                    vec![Statement {
+                        id: 1,
+                        op: Box::new(NoteProbe) as Box<dyn Op>,
+                    }]
+                    .into_boxed_slice()
+                })
+                .collect(),
+            2,
+            1,
+        )
+    }
+
+    fn note_frame(poly: &mut Poly, note: Sample, velocity: Sample, ctl: Sample) -> Sample {
+        let mut stack = Stack::new();
+        stack.push(&[note; CHANNELS]);
+        stack.push(&[velocity; CHANNELS]);
+        stack.push(&[ctl; CHANNELS]);
+        poly.perform(&mut stack);
+        stack.peek()[0]
+    }
+
+    #[test]
+    fn latches_every_value_per_voice() {
+        let mut poly = note_poly(2);
+        assert_eq!(note_frame(&mut poly, 60.0, 0.5, 1.0), 60.0 + 50.0 + 10.0);
+        // Both values stay latched while the inputs move on.
+        assert_eq!(note_frame(&mut poly, 0.0, 0.0, 0.0), 110.0);
+        assert_eq!(
+            note_frame(&mut poly, 64.0, 0.25, 1.0),
+            110.0 + 64.0 + 25.0 + 10.0
+        );
+    }
+
+    #[test]
+    fn migrate_keeps_every_latched_value() {
+        let mut old = note_poly(2);
+        note_frame(&mut old, 60.0, 0.5, 1.0);
+        note_frame(&mut old, 0.0, 0.0, 0.0);
+        let mut new = note_poly(2);
+        new.migrate(&mut old);
+        assert_eq!(note_frame(&mut new, 0.0, 0.0, 0.0), 110.0);
+    }
+
+    #[test]
+    fn idle_detection_prefers_free_voices() {
+        let mut poly = probe_poly(2);
+        poly.set_idle_hold(Some(2));
+        assert_eq!(frame(&mut poly, 60.0, 1.0), [70.0, 70.0]);
+        assert_eq!(frame(&mut poly, 0.0, 0.0), [60.0, 60.0]);
+        // Voice 1 plays a silent note and goes quiet for the hold time.
+        assert_eq!(frame(&mut poly, 0.0, 1.0), [70.0, 70.0]);
+        frame(&mut poly, 0.0, 0.0);
+        frame(&mut poly, 0.0, 0.0);
+        // Round-robin would steal the ringing voice 0; voice 1 is free.
+        assert_eq!(frame(&mut poly, 72.0, 1.0), [142.0, 142.0]);
+    }
+
+    /// Counts how many times the body runs.
+    struct Runs(Arc<AtomicUsize>);
+
+    impl Op for Runs {
+        fn perform(&mut self, stack: &mut Stack) {
+            stack.pop();
+            stack.pop();
+            self.0.fetch_add(1, Ordering::Relaxed);
+            stack.push(&SILENCE);
+        }
+    }
+
+    #[test]
+    fn idle_voices_are_skipped() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let mut poly = Poly::new(
+            (0..4)
+                .map(|_| {
+                    vec![Statement {
+                        id: 1,
+                        op: Box::new(Runs(Arc::clone(&runs))) as Box<dyn Op>,
+                    }]
+                    .into_boxed_slice()
+                })
+                .collect(),
+        );
+        poly.set_idle_hold(Some(1));
+        // Never-used voices are free.
+        frame(&mut poly, 0.0, 0.0);
+        assert_eq!(runs.load(Ordering::Relaxed), 0);
+        // One held note runs one body until it has been quiet for the hold.
+        frame(&mut poly, 60.0, 1.0);
+        frame(&mut poly, 60.0, 1.0);
+        frame(&mut poly, 60.0, 0.0);
+        frame(&mut poly, 60.0, 0.0);
+        assert_eq!(runs.load(Ordering::Relaxed), 3);
+    }
+
+    /// Pops spread and input, pushes `input + spread`.
+    struct Spread;
+
+    impl Op for Spread {
+        fn perform(&mut self, stack: &mut Stack) {
+            let spread = stack.pop();
+            let input = stack.pop();
+            stack.push(&[input[0] + spread[0], input[1] + spread[1]]);
+        }
+    }
+
+    fn unison(copies: usize, width: Option<Sample>) -> Unison {
+        Unison::new(
+            (0..copies)
+                .map(|_| {
+                    vec![Statement {
+                        id: 1,
+                        op: Box::new(Spread) as Box<dyn Op>,
+                    }]
+                    .into_boxed_slice()
+                })
+                .collect(),
+            width,
+        )
+    }
+
+    fn unison_frame(op: &mut Unison, input: Sample) -> Frame {
+        let mut stack = Stack::new();
+        stack.push(&[input; CHANNELS]);
+        op.perform(&mut stack);
+        stack.pop()
+    }
+
+    #[test]
+    fn unison_spreads_copies_over_minus_one_to_one() {
+        // Spreads -1, 0, 1 cancel out: 3 * 10.
+        assert_eq!(unison_frame(&mut unison(3, None), 10.0), [30.0, 30.0]);
+        // A single copy sits in the centre.
+        assert_eq!(unison_frame(&mut unison(1, None), 10.0), [10.0, 10.0]);
+    }
+
+    #[test]
+    fn unison_width_pans_copies_by_spread() {
+        let close = |a: Frame, b: Frame| a.iter().zip(&b).all(|(a, b)| (a - b).abs() < 1e-9);
+        // Full width: copy 0 (output 1) hard left, copy 1 (output 3) hard
+        // right, each at equal power.
+        let sqrt_2 = std::f64::consts::SQRT_2;
+        assert!(close(
+            unison_frame(&mut unison(2, Some(1.0)), 2.0),
+            [sqrt_2, 3.0 * sqrt_2]
+        ));
+        // Zero width leaves copies centred at unity gain.
+        assert!(close(
+            unison_frame(&mut unison(2, Some(0.0)), 2.0),
+            [4.0, 4.0]
+        ));
+    }
+
+    #[test]
+    fn separate_outputs_push_one_frame_per_voice() {
+        let mut poly = probe_poly(3);
+        poly.set_separate_outputs(Some(3));
+        let mut stack = Stack::new();
+        stack.push(&[60.0; CHANNELS]);
+        stack.push(&[1.0; CHANNELS]);
+        poly.perform(&mut stack);
+        assert_eq!(stack.pop(), SILENCE);
+        assert_eq!(stack.pop(), SILENCE);
+        assert_eq!(stack.pop(), [70.0, 70.0]);
+
+        let mut empty = Poly::empty();
+        empty.set_separate_outputs(Some(2));
+        stack.push(&[5.0; CHANNELS]);
+        stack.push(&[60.0; CHANNELS]);
+        stack.push(&[1.0; CHANNELS]);
+        empty.perform(&mut stack);
+        assert_eq!(stack.pop(), SILENCE);
+        assert_eq!(stack.pop(), SILENCE);
+        assert_eq!(stack.pop(), [5.0, 5.0]);
+    }
+
     #[test]
     fn empty_poly_consumes_inputs_and_pushes_silence() {
         let mut poly = Poly::empty();
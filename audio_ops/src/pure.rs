@@ -245,6 +245,15 @@ pub fn pan(l: Sample, r: Sample, c: Sample) -> (Sample, Sample) {
     )
 }
 
+/// Equal-power pan of a mono signal to position `c` in `-1..1`, unity gain
+/// per channel at the centre.
+#[inline]
+pub fn equal_power_pan(x: Sample, c: Sample) -> (Sample, Sample) {
+    let angle = (c.clamp(-1.0, 1.0) + 1.0) * std::f64::consts::FRAC_PI_4;
+    let x = x * std::f64::consts::SQRT_2;
+    (angle.cos() * x, angle.sin() * x)
+}
+
 /// Chebyshev polynomial of degree 2
 /// T_2(x) = 2x^2 - 1
 #[inline]
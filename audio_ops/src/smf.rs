@@ -0,0 +1,420 @@
+//! Standard MIDI Files (format 0 and 1) for offline rendering. Tracks are
+//! merged into one time-ordered list and ticks are converted to seconds
+//! through the file's tempo map. Recordings are written back as format 0
+//! files whose ticks are sample frames.
+
+use crate::midi::{MidiEvent, MidiEventKind, decode_message, encode_message};
+
+/// Microseconds per quarter note until the first Set Tempo event (120 BPM).
+const DEFAULT_TEMPO: u32 = 500_000;
+const END_OF_TRACK: u8 = 0x2f;
+const SET_TEMPO: u8 = 0x51;
+const TEXT: u8 = 0x01;
+/// Largest delta time a four-byte variable-length quantity holds.
+const MAX_DELTA: u64 = 0x0fff_ffff;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SmfError {
+    NotSmf,
+    /// Format 2 (independent sequences) or unknown.
+    UnsupportedFormat(u16),
+    /// An SMPTE division with a frame rate other than 24, 25, 29.97 or 30.
+    UnsupportedDivision(u16),
+    Truncated,
+    /// A data byte with no running status to apply it to.
+    MissingStatus,
+    UnexpectedStatus(u8),
+}
+
+impl std::fmt::Display for SmfError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SmfError::NotSmf => write!(f, "not a Standard MIDI File"),
+            SmfError::UnsupportedFormat(format) => {
+                write!(f, "unsupported MIDI file format {format}")
+            }
+            SmfError::UnsupportedDivision(division) => {
+                write!(f, "unsupported MIDI file time division {division:#06x}")
+            }
+            SmfError::Truncated => write!(f, "truncated MIDI file"),
+            SmfError::MissingStatus => write!(f, "MIDI data byte without a status"),
+            SmfError::UnexpectedStatus(status) => {
+                write!(f, "unexpected MIDI status byte {status:#04x}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SmfError {}
+
+/// A file event and its time in seconds from the start of the file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SmfEvent {
+    pub time: f64,
+    pub event: MidiEvent,
+}
+
+#[derive(Clone, Copy)]
+enum Division {
+    TicksPerQuarter(u16),
+    /// SMPTE time code: tempo events don't apply.
+    TicksPerSecond(f64),
+}
+
+impl Division {
+    fn seconds_per_tick(self, tempo: u32) -> f64 {
+        match self {
+            Division::TicksPerQuarter(ticks) => f64::from(tempo) * 1e-6 / f64::from(ticks),
+            Division::TicksPerSecond(ticks) => ticks.recip(),
+        }
+    }
+}
+
+/// Note, controller, pressure and pitch bend events of all tracks. Events
+/// on the same tick keep their file order, tracks in order of appearance.
+pub fn parse_smf(bytes: &[u8]) -> Result<Vec<SmfEvent>, SmfError> {
+    if !bytes.starts_with(b"MThd") {
+        return Err(SmfError::NotSmf);
+    }
+    let mut reader = Reader::new(&bytes[4..]);
+    let header_len = reader.u32()? as usize;
+    let mut header = Reader::new(reader.take(header_len)?);
+    let format = header.u16()?;
+    let _tracks = header.u16()?;
+    let division = header.u16()?;
+    if format > 1 {
+        return Err(SmfError::UnsupportedFormat(format));
+    }
+    let division = if division & 0x8000 == 0 {
+        Division::TicksPerQuarter(division.max(1))
+    } else {
+        // The high byte is the negated frame rate, -29 meaning 29.97 drop
+        // frame.
+        let fps = match (division >> 8) as u8 as i8 {
+            -24 => 24.0,
+            -25 => 25.0,
+            -29 => 29.97,
+            -30 => 30.0,
+            _ => return Err(SmfError::UnsupportedDivision(division)),
+        };
+        Division::TicksPerSecond((fps * f64::from(division & 0xff)).max(1.0))
+    };
+
+    let mut events = Vec::new();
+    let mut tempos = Vec::new();
+    while !reader.is_empty() {
+        let id = reader.take(4)?;
+        let len = reader.u32()? as usize;
+        let chunk = reader.take(len)?;
+        // Unknown chunks are skipped as the spec asks.
+        if id == b"MTrk" {
+            read_track(chunk, &mut events, &mut tempos)?;
+        }
+    }
+    // Stable, so same-tick events keep their order.
+    events.sort_by_key(|&(tick, _)| tick);
+    tempos.sort_by_key(|&(tick, _)| tick);
+
+    let mut tempos = tempos.into_iter().peekable();
+    let (mut base_tick, mut base_time, mut tempo) = (0, 0.0, DEFAULT_TEMPO);
+    Ok(events
+        .into_iter()
+        .map(|(tick, event)| {
+            while let Some((change_tick, change)) = tempos.next_if(|&(change, _)| change <= tick) {
+                base_time += (change_tick - base_tick) as f64 * division.seconds_per_tick(tempo);
+                base_tick = change_tick;
+                tempo = change;
+            }
+            SmfEvent {
+                time: base_time + (tick - base_tick) as f64 * division.seconds_per_tick(tempo),
+                event,
+            }
+        })
+        .collect())
+}
+
+fn read_track(
+    chunk: &[u8],
+    events: &mut Vec<(u64, MidiEvent)>,
+    tempos: &mut Vec<(u64, u32)>,
+) -> Result<(), SmfError> {
+    let mut reader = Reader::new(chunk);
+    let mut tick = 0;
+    let mut running_status = None;
+    while !reader.is_empty() {
+        tick += u64::from(reader.varlen()?);
+        let status = if reader.peek()? & 0x80 != 0 {
+            reader.u8()?
+        } else {
+            running_status.ok_or(SmfError::MissingStatus)?
+        };
+        match status {
+            0xff => {
+                running_status = None;
+                let kind = reader.u8()?;
+                let len = reader.varlen()? as usize;
+                let data = reader.take(len)?;
+                match (kind, data) {
+                    (END_OF_TRACK, _) => break,
+                    (SET_TEMPO, &[a, b, c]) => {
+                        tempos.push((tick, u32::from_be_bytes([0, a, b, c]).max(1)))
+                    }
+                    _ => {}
+                }
+            }
+            0xf0 | 0xf7 => {
+                running_status = None;
+                let len = reader.varlen()? as usize;
+                reader.take(len)?;
+            }
+            0x80..=0xef => {
+                running_status = Some(status);
+                let len = match status & 0xf0 {
+                    0xc0 | 0xd0 => 1,
+                    _ => 2,
+                };
+                let mut message = [status, 0, 0];
+                message[1..=len].copy_from_slice(reader.take(len)?);
+                // Program changes and poly aftertouch have no event kind.
+                if let Some(event) = decode_message(&message[..=len]) {
+                    events.push((tick, event));
+                }
+            }
+            _ => return Err(SmfError::UnexpectedStatus(status)),
+        }
+    }
+    Ok(())
+}
+
+/// A format 0 file of `events` stamped with the frame they played on, with
+/// its end of track at `frames`. Real-time messages have no place in a file
+/// and are left out.
+pub fn write_smf(events: &[(u64, MidiEvent)], frames: u64, sample_rate: u32) -> Vec<u8> {
+    // One tick per frame: 120 BPM, or faster where half the sample rate
+    // doesn't fit the 15 bit division.
+    let (mut division, mut tempo) = (sample_rate / 2, DEFAULT_TEMPO);
+    while division > 0x7fff {
+        division /= 2;
+        tempo /= 2;
+    }
+
+    let mut track = Vec::new();
+    track.extend([0x00, 0xff, SET_TEMPO, 0x03]);
+    track.extend(&tempo.to_be_bytes()[1..]);
+    let mut tick = 0;
+    let mut write_delta = |track: &mut Vec<u8>, to: u64| {
+        let mut delta = to.saturating_sub(tick);
+        // Longer gaps are bridged with empty text events.
+        while delta > MAX_DELTA {
+            write_varlen(track, MAX_DELTA as u32);
+            track.extend([0xff, TEXT, 0x00]);
+            delta -= MAX_DELTA;
+        }
+        write_varlen(track, delta as u32);
+        tick = tick.max(to);
+    };
+    for (frame, event) in events {
+        if matches!(
+            event.kind,
+            MidiEventKind::Clock
+                | MidiEventKind::Start
+                | MidiEventKind::Continue
+                | MidiEventKind::Stop
+        ) {
+            continue;
+        }
+        let (message, len) = encode_message(event);
+        write_delta(&mut track, *frame);
+        track.extend(&message[..len]);
+    }
+    write_delta(&mut track, frames);
+    track.extend([0xff, END_OF_TRACK, 0x00]);
+
+    let mut bytes = b"MThd".to_vec();
+    bytes.extend(6u32.to_be_bytes());
+    bytes.extend(0u16.to_be_bytes());
+    bytes.extend(1u16.to_be_bytes());
+    bytes.extend((division.max(1) as u16).to_be_bytes());
+    bytes.extend(b"MTrk");
+    bytes.extend((track.len() as u32).to_be_bytes());
+    bytes.extend(track);
+    bytes
+}
+
+fn write_varlen(bytes: &mut Vec<u8>, value: u32) {
+    for shift in [21, 14, 7] {
+        if value >> shift != 0 {
+            bytes.push((value >> shift & 0x7f) as u8 | 0x80);
+        }
+    }
+    bytes.push((value & 0x7f) as u8);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], SmfError> {
+        if len > self.bytes.len() {
+            return Err(SmfError::Truncated);
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn peek(&self) -> Result<u8, SmfError> {
+        self.bytes.first().copied().ok_or(SmfError::Truncated)
+    }
+
+    fn u8(&mut self) -> Result<u8, SmfError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, SmfError> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, SmfError> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    /// Variable-length quantity, at most four bytes.
+    fn varlen(&mut self) -> Result<u32, SmfError> {
+        let mut value = 0;
+        for _ in 0..4 {
+            let byte = self.u8()?;
+            value = value << 7 | u32::from(byte & 0x7f);
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(SmfError::Truncated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::midi::MidiEventKind;
+
+    fn smf(format: u16, division: u16, tracks: &[&[u8]]) -> Vec<u8> {
+        let mut bytes = b"MThd".to_vec();
+        bytes.extend(6u32.to_be_bytes());
+        bytes.extend(format.to_be_bytes());
+        bytes.extend((tracks.len() as u16).to_be_bytes());
+        bytes.extend(division.to_be_bytes());
+        for track in tracks {
+            bytes.extend(b"MTrk");
+            bytes.extend((track.len() as u32).to_be_bytes());
+            bytes.extend(*track);
+        }
+        bytes
+    }
+
+    #[test]
+    fn merges_tracks_through_the_tempo_map() {
+        // 96 ticks per quarter: 120 BPM for the first beat, then 60 BPM.
+        let tempo: &[u8] = &[
+            0x00, 0xff, 0x51, 0x03, 0x07, 0xa1, 0x20, //
+            0x60, 0xff, 0x51, 0x03, 0x0f, 0x42, 0x40, //
+            0x00, 0xff, 0x2f, 0x00,
+        ];
+        let notes: &[u8] = &[
+            0x00, 0x90, 60, 127, // note on
+            0x00, 64, 127, // running status
+            0x60, 0x80, 60, 0, // one beat later
+            0x00, 0xc0, 5, // program change, ignored
+            0x81, 0x40, 0xe1, 0x00, 0x40, // varlen 192 ticks, centred bend
+            0x00, 0xb1, 74, 0, //
+            0x00, 0xff, 0x2f, 0x00,
+        ];
+        let events = parse_smf(&smf(1, 96, &[tempo, notes])).unwrap();
+        let summary = events
+            .iter()
+            .map(|e| (e.time, e.event.kind, e.event.channel, e.event.note))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [
+                (0.0, MidiEventKind::NoteOn, 0, 60),
+                (0.0, MidiEventKind::NoteOn, 0, 64),
+                (0.5, MidiEventKind::NoteOff, 0, 60),
+                (2.5, MidiEventKind::PitchBend, 1, 0),
+                (2.5, MidiEventKind::ControlChange, 1, 74),
+            ]
+        );
+        assert_eq!(events[0].event.velocity, 1.0);
+        assert_eq!(events[3].event.velocity, 0.0);
+    }
+
+    #[test]
+    fn reads_smpte_division() {
+        // 25 fps, 40 ticks per frame: a millisecond per tick.
+        let track: &[u8] = &[0x83, 0x74, 0x90, 60, 100, 0x00, 0xff, 0x2f, 0x00];
+        let events = parse_smf(&smf(0, 0xe728, &[track])).unwrap();
+        assert_eq!(events.len(), 1);
+        assert!((events[0].time - 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn rejects_other_files_and_formats() {
+        assert_eq!(parse_smf(b"RIFF"), Err(SmfError::NotSmf));
+        assert_eq!(
+            parse_smf(&smf(2, 96, &[])),
+            Err(SmfError::UnsupportedFormat(2))
+        );
+        for division in [0x8028, 0xe628, 0xff28] {
+            assert_eq!(
+                parse_smf(&smf(0, division, &[])),
+                Err(SmfError::UnsupportedDivision(division))
+            );
+        }
+        let truncated = smf(0, 96, &[&[0x00, 0x90, 60]]);
+        assert_eq!(parse_smf(&truncated), Err(SmfError::Truncated));
+        let orphan = smf(0, 96, &[&[0x00, 60, 100]]);
+        assert_eq!(parse_smf(&orphan), Err(SmfError::MissingStatus));
+    }
+
+    #[test]
+    fn recordings_round_trip_on_exact_frames() {
+        let events = [
+            (0, MidiEvent::note_on(0, 60, 1.0)),
+            (1, MidiEvent::realtime(MidiEventKind::Clock)),
+            (12_345, MidiEvent::control_change(2, 74, 0.0)),
+            (12_345, MidiEvent::pitch_bend(2, 0.0)),
+            (48_001, MidiEvent::note_off(0, 60)),
+        ];
+        for sample_rate in [44_100, 48_000, 96_000] {
+            let bytes = write_smf(&events, 96_000, sample_rate);
+            let parsed = parse_smf(&bytes).unwrap();
+            let frames = parsed
+                .iter()
+                .map(|e| ((e.time * f64::from(sample_rate)).round() as u64, e.event))
+                .collect::<Vec<_>>();
+            let expected = events
+                .iter()
+                .filter(|(_, event)| event.kind != MidiEventKind::Clock)
+                .copied()
+                .collect::<Vec<_>>();
+            assert_eq!(frames, expected);
+        }
+    }
+
+    #[test]
+    fn long_gaps_are_bridged() {
+        let frames = MAX_DELTA * 2 + 5;
+        let bytes = write_smf(&[(frames, MidiEvent::note_on(0, 60, 1.0))], frames, 48_000);
+        let parsed = parse_smf(&bytes).unwrap();
+        assert_eq!((parsed[0].time * 48_000.0).round() as u64, frames);
+    }
+}
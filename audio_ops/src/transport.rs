@@ -0,0 +1,181 @@
+//! # Shared transport
+//!
+//! Opt-in musical clock owned by whoever drives the VM (the audio server or
+//! an offline renderer), not by individual ops: the owner calls
+//! `Transport::advance` after each `next_frame` that ran the program (see
+//! `VM::is_running`), so the position holds while paused, and `beat`, `bar`,
+//! `beatphase` and `barphase` read the position for the current frame. The
+//! position lives in the compilation `Context`, so it survives recompiles.
+use audio_vm::{AtomicSample, CHANNELS, Op, Sample, Stack};
+use std::sync::{Arc, atomic::Ordering};
+
+const DEFAULT_BPM: Sample = 120.0;
+const DEFAULT_METER: Sample = 4.0;
+
+pub struct Transport {
+    /// Position in beats since the last reset.
+    position: AtomicSample,
+    bpm: AtomicSample,
+    /// Beats per bar.
+    meter: AtomicSample,
+}
+
+impl Default for Transport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Transport {
+    pub fn new() -> Self {
+        Transport {
+            position: AtomicSample::new(0.0f64.to_bits()),
+            bpm: AtomicSample::new(DEFAULT_BPM.to_bits()),
+            meter: AtomicSample::new(DEFAULT_METER.to_bits()),
+        }
+    }
+
+    /// Move the position forward by one frame. Must be called from a single
+    /// thread (the one running the VM).
+    pub fn advance(&self, sample_period: Sample) {
+        let beats = self.bpm() / 60.0 * sample_period;
+        self.position
+            .store((self.position() + beats).to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn reset(&self) {
+        self.position.store(0.0f64.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Ignores non-finite and non-positive tempos.
+    pub fn set_bpm(&self, bpm: Sample) {
+        if bpm.is_finite() && bpm > 0.0 {
+            self.bpm.store(bpm.to_bits(), Ordering::Relaxed);
+        }
+    }
+
+    /// Ignores meters below one beat per bar.
+    pub fn set_meter(&self, beats: u32) {
+        if beats > 0 {
+            self.meter
+                .store(Sample::from(beats).to_bits(), Ordering::Relaxed);
+        }
+    }
+
+    pub fn position(&self) -> Sample {
+        f64::from_bits(self.position.load(Ordering::Relaxed))
+    }
+
+    pub fn bpm(&self) -> Sample {
+        f64::from_bits(self.bpm.load(Ordering::Relaxed))
+    }
+
+    pub fn meter(&self) -> Sample {
+        f64::from_bits(self.meter.load(Ordering::Relaxed))
+    }
+
+    /// Beat index within the current bar.
+    pub fn beat(&self) -> Sample {
+        self.position().floor().rem_euclid(self.meter())
+    }
+
+    /// Bars since the last reset.
+    pub fn bar(&self) -> Sample {
+        (self.position() / self.meter()).floor()
+    }
+
+    pub fn beat_phase(&self) -> Sample {
+        self.position().rem_euclid(1.0)
+    }
+
+    pub fn bar_phase(&self) -> Sample {
+        (self.position() / self.meter()).rem_euclid(1.0)
+    }
+}
+
+/// Reads one view of the shared transport and broadcasts it to both channels.
+pub struct TransportValue {
+    transport: Arc<Transport>,
+    read: fn(&Transport) -> Sample,
+}
+
+impl TransportValue {
+    pub fn beat(transport: Arc<Transport>) -> Self {
+        TransportValue {
+            transport,
+            read: Transport::beat,
+        }
+    }
+
+    pub fn bar(transport: Arc<Transport>) -> Self {
+        TransportValue {
+            transport,
+            read: Transport::bar,
+        }
+    }
+
+    pub fn beat_phase(transport: Arc<Transport>) -> Self {
+        TransportValue {
+            transport,
+            read: Transport::beat_phase,
+        }
+    }
+
+    pub fn bar_phase(transport: Arc<Transport>) -> Self {
+        TransportValue {
+            transport,
+            read: Transport::bar_phase,
+        }
+    }
+}
+
+impl Op for TransportValue {
+    fn perform(&mut self, stack: &mut Stack) {
+        stack.push(&[(self.read)(&self.transport); CHANNELS]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(op: &mut TransportValue) -> Sample {
+        let mut stack = Stack::new();
+        op.perform(&mut stack);
+        stack.pop()[0]
+    }
+
+    #[test]
+    fn advances_by_tempo_and_splits_into_bars_and_beats() {
+        let transport = Arc::new(Transport::new());
+        transport.set_bpm(60.0);
+        transport.set_meter(3);
+        let mut beat = TransportValue::beat(Arc::clone(&transport));
+        let mut bar = TransportValue::bar(Arc::clone(&transport));
+        let mut beat_phase = TransportValue::beat_phase(Arc::clone(&transport));
+        let mut bar_phase = TransportValue::bar_phase(Arc::clone(&transport));
+
+        // 60 bpm at 4 frames per second: a beat every 4 frames.
+        for _ in 0..18 {
+            transport.advance(0.25);
+        }
+        assert_eq!(read(&mut beat), 1.0);
+        assert_eq!(read(&mut bar), 1.0);
+        assert_eq!(read(&mut beat_phase), 0.5);
+        assert_eq!(read(&mut bar_phase), 0.5);
+
+        transport.reset();
+        assert_eq!(read(&mut bar), 0.0);
+        assert_eq!(read(&mut bar_phase), 0.0);
+    }
+
+    #[test]
+    fn ignores_invalid_tempo_and_meter() {
+        let transport = Transport::new();
+        transport.set_bpm(Sample::NAN);
+        transport.set_bpm(-10.0);
+        transport.set_meter(0);
+        assert_eq!(transport.bpm(), DEFAULT_BPM);
+        assert_eq!(transport.meter(), DEFAULT_METER);
+    }
+}
@@ -2,7 +2,7 @@ use ahash::RandomState;
 use audio_ops::*;
 #[cfg(test)]
 use audio_vm::Frame;
-use audio_vm::{AtomicFrame, AtomicSample, Op, Program, Sample, Statement};
+use audio_vm::{AtomicFrame, AtomicSample, Op, Program, STACK_SIZE, Sample, Statement};
 use regex::Regex;
 use rkyv::{Archive, Deserialize as RkyvDeserialize, Serialize as RkyvSerialize};
 use serde::{Deserialize, Serialize};
@@ -25,8 +25,15 @@ pub struct Context {
     pub tables: HashMap<String, Arc<Vec<AtomicFrame>>, RandomState>,
     pub variables: HashMap<String, Arc<AtomicFrame>, RandomState>,
     pub midi: Arc<MidiFrameEvents>,
+    /// Events queued by `midiout:`/`ccout:`, drained by the VM owner.
+    pub midi_out: Arc<MidiOutEvents>,
+    /// Shared transport; advanced by the VM owner, so it outlives programs.
+    pub transport: Arc<Transport>,
     pub seed: Option<u64>,
     pub rng_counter: u64,
+    /// `(index, count)` of the voice body instance being compiled, read by
+    /// `vi`/`vn`; `None` at top level.
+    pub voice: Option<(usize, usize)>,
 }
 
 impl Context {
@@ -37,8 +44,11 @@ impl Context {
             tables: HashMap::with_hasher(RandomState::new()),
             variables: HashMap::with_hasher(RandomState::new()),
             midi: Arc::new(MidiFrameEvents::new()),
+            midi_out: Arc::new(MidiOutEvents::new()),
+            transport: Arc::new(Transport::new()),
             seed: None,
             rng_counter: 0,
+            voice: None,
         }
     }
 }
@@ -235,8 +245,15 @@ fn is_quotation_consumer(op: &str) -> bool {
     op == "drop"
         || op == "poly"
         || op.starts_with("poly:")
+        || op == "polyx"
+        || op.starts_with("polyx:")
         || op == "mpoly"
         || op.starts_with("mpoly:")
+        || op == "mmono"
+        || op.starts_with("mmono:")
+        || op.starts_with("mmono@")
+        || op == "uni"
+        || op.starts_with("uni:")
         || template_definition_name(op).is_some()
 }
 
@@ -245,9 +262,10 @@ pub fn compile_program(ops: &[TextOp], sample_rate: u32, ctx: &mut Context) -> P
         .iter()
         .find_map(|op| op.op.strip_prefix("seed:")?.parse::<u64>().ok());
     ctx.set_seed(seed);
+    set_transport(ops, ctx);
     let ops = ops
         .iter()
-        .filter(|op| !op.op.starts_with("seed:"))
+        .filter(|op| !is_directive(&op.op))
         .cloned()
         .collect::<Vec<_>>();
     let ops = rewrite_terms(&ops);
@@ -256,6 +274,29 @@ pub fn compile_program(ops: &[TextOp], sample_rate: u32, ctx: &mut Context) -> P
     program
 }
 
+fn is_directive(op: &str) -> bool {
+    op.starts_with("seed:") || op.starts_with("bpm:") || op.starts_with("meter:")
+}
+
+/// Apply `bpm:` and `meter:` directives to the shared transport. Programs
+/// without them leave the transport as it is, so tempo set by the server
+/// survives recompiles.
+fn set_transport(ops: &[TextOp], ctx: &Context) {
+    for op in ops {
+        if let Some(bpm) = op.op.strip_prefix("bpm:") {
+            match bpm.parse::<Sample>() {
+                Ok(bpm) if bpm.is_finite() && bpm > 0.0 => ctx.transport.set_bpm(bpm),
+                _ => log::warn!("Can't parse {} as tempo; ignoring it.", op.op),
+            }
+        } else if let Some(meter) = op.op.strip_prefix("meter:") {
+            match meter.parse::<u32>() {
+                Ok(meter) if meter > 0 => ctx.transport.set_meter(meter),
+                _ => log::warn!("Can't parse {} as meter; ignoring it.", op.op),
+            }
+        }
+    }
+}
+
 /// Compile an op stream: quotations (`QUOTE_OPEN .. QUOTE_CLOSE` followed by
 /// a quotation consumer) become container ops, everything between them is
 /// compiled as plain segments. Returns true if compilation was stopped by
@@ -294,7 +335,12 @@ fn compile_ops(ops: &[TextOp], sample_rate: u32, ctx: &mut Context, program: &mu
         };
         let body = &ops[i + 1..close];
         match ops.get(close + 1) {
-            Some(consumer) if consumer.op == "poly" || consumer.op.starts_with("poly:") => {
+            Some(consumer)
+                if consumer.op == "poly"
+                    || consumer.op.starts_with("poly:")
+                    || consumer.op == "polyx"
+                    || consumer.op.starts_with("polyx:") =>
+            {
                 program.push(Statement {
                     id: consumer.id,
                     op: Box::new(compile_poly(&consumer.op, body, sample_rate, ctx)),
@@ -308,8 +354,28 @@ fn compile_ops(ops: &[TextOp], sample_rate: u32, ctx: &mut Context, program: &mu
                 });
                 i = close + 2;
             }
+            Some(consumer)
+                if consumer.op == "mmono"
+                    || consumer.op.starts_with("mmono:")
+                    || consumer.op.starts_with("mmono@") =>
+            {
+                program.push(Statement {
+                    id: consumer.id,
+                    op: Box::new(compile_mmono(&consumer.op, body, sample_rate, ctx)),
+                });
+                i = close + 2;
+            }
+            Some(consumer) if consumer.op == "uni" || consumer.op.starts_with("uni:") => {
+                program.push(Statement {
+                    id: consumer.id,
+                    op: Box::new(compile_unison(&consumer.op, body, sample_rate, ctx)),
+                });
+                i = close + 2;
+            }
             _ => {
-                log::warn!("Quotation marker is not followed by poly/mpoly; ignoring it.");
+                log::warn!(
+                    "Quotation marker is not followed by poly/mpoly/mmono/uni; ignoring it."
+                );
                 i = close + 1;
             }
         }
@@ -321,26 +387,72 @@ fn compile_ops(ops: &[TextOp], sample_rate: u32, ctx: &mut Context, program: &mu
 /// Compile `<quotation> poly:N`: N voices, each an independently compiled
 /// instance of the body sharing node ids (state migrates by voice index +
 /// node id). Invalid argument or empty body compiles to a forgiving
-/// zero-voice op which preserves stack shape.
+/// zero-voice op which preserves stack shape. `polyx:N` pushes one frame per
+/// voice instead of the sum, capped at the stack depth.
 fn compile_poly(op: &str, body: &[TextOp], sample_rate: u32, ctx: &mut Context) -> Poly {
-    let Some(voices) = parse_voice_count(op) else {
+    let (values, lanes) = parse_poly_inputs(op);
+    let idle = op.ends_with(":idle");
+    let separate = op.starts_with("polyx");
+    let Some(mut voices) = parse_poly_voice_count(op) else {
         log::warn!(
             "Can't parse voice count in {}; compiling to a zero-voice poly.",
             op
         );
-        return Poly::empty();
+        return Poly::empty_with_inputs(values, lanes);
     };
+    if separate && voices > STACK_SIZE {
+        log::warn!(
+            "{} pushes more frames than the stack holds; compiling {} voices.",
+            op,
+            STACK_SIZE
+        );
+        voices = STACK_SIZE;
+    }
+    // All voices fit only while at most `STACK_SIZE - voices` frames sit below
+    // the inputs, otherwise `Stack::push` drops them. That depth isn't tracked
+    // at compile time, so warn once the room is smaller than the inputs.
+    let inputs = values * lanes + 1;
+    if separate && voices > STACK_SIZE - inputs {
+        log::warn!(
+            "{} pushes {} frames; voices are dropped unless at most {} frames sit below its {} inputs.",
+            op,
+            voices,
+            STACK_SIZE - voices,
+            inputs
+        );
+    }
     let bodies = compile_voice_bodies(voices, body, sample_rate, ctx);
-    if bodies.first().is_none_or(|body| body.is_empty()) {
+    let mut poly = if bodies.first().is_none_or(|body| body.is_empty()) {
         log::warn!("Empty poly voice body; compiling to a zero-voice poly.");
-        return Poly::empty();
-    }
-    Poly::new(bodies)
+        Poly::empty_with_inputs(values, lanes)
+    } else {
+        Poly::with_inputs(bodies, values, lanes)
+    };
+    poly.set_idle_hold(idle.then_some((IDLE_HOLD_SECONDS * sample_rate as Sample) as usize));
+    poly.set_separate_outputs(separate.then_some(voices));
+    poly
 }
 
+/// Compile `<quotation> mpoly:N[:mpe][@QUALIFIER...]`, where qualifiers
+/// restrict note-ons to channel sets (`@ch1`, `@ch2-4,6`) and key ranges
+/// (`@C1-B2`, `@60-127`).
 fn compile_mpoly(op: &str, body: &[TextOp], sample_rate: u32, ctx: &mut Context) -> MPoly {
     let midi = Arc::clone(&ctx.midi);
-    let Some(voices) = parse_voice_count(op) else {
+    let (op, qualifiers) = op
+        .split_once('@')
+        .map_or((op, None), |(op, q)| (op, Some(q)));
+    let filter = match qualifiers.map(parse_midi_filter) {
+        None => MidiFilter::default(),
+        Some(Some(filter)) => filter,
+        Some(None) => {
+            log::warn!(
+                "Can't parse channel/key qualifiers in {}; compiling to a zero-output mpoly.",
+                op
+            );
+            return MPoly::empty(midi);
+        }
+    };
+    let Some(voices) = parse_voice_count(op.split(':').nth(1).unwrap_or("")) else {
         log::warn!(
             "Can't parse voice count in {}; compiling to a zero-output mpoly.",
             op
@@ -352,14 +464,216 @@ fn compile_mpoly(op: &str, body: &[TextOp], sample_rate: u32, ctx: &mut Context)
         log::warn!("Empty mpoly voice body; compiling to a zero-output mpoly.");
         return MPoly::empty(midi);
     }
-    MPoly::new(bodies, midi)
+    let mut mpoly = MPoly::new(bodies, midi);
+    match op.split(':').nth(2) {
+        None => {}
+        Some("mpe") => mpoly.set_mpe(true),
+        Some(mode) => log::warn!("Unknown mpoly mode {} in {}; ignoring it.", mode, op),
+    }
+    mpoly.set_filter(filter);
+    mpoly
+}
+
+/// Compile `<quotation> mmono[:last|low|high[:GLIDE]][@QUALIFIER...]`: one
+/// legato MIDI voice with note priority (default last) and an optional glide
+/// time in seconds, taking the same qualifiers as `mpoly`.
+fn compile_mmono(op: &str, body: &[TextOp], sample_rate: u32, ctx: &mut Context) -> MMono {
+    let midi = Arc::clone(&ctx.midi);
+    let (op, qualifiers) = op
+        .split_once('@')
+        .map_or((op, None), |(op, q)| (op, Some(q)));
+    let filter = match qualifiers.map(parse_midi_filter) {
+        None => Some(MidiFilter::default()),
+        Some(filter) => filter,
+    };
+    let tokens: Vec<&str> = op.split(':').collect();
+    let priority = match tokens.get(1).copied() {
+        None | Some("last") => Some(NotePriority::Last),
+        Some("low") => Some(NotePriority::Low),
+        Some("high") => Some(NotePriority::High),
+        Some(_) => None,
+    };
+    let glide = parse_midi_smoothing(tokens.get(2).copied()).filter(|_| tokens.len() <= 3);
+    let (Some(filter), Some(priority), Some(glide)) = (filter, priority, glide) else {
+        log::warn!(
+            "Can't parse mmono arguments in {}; compiling to a zero-output mmono.",
+            op
+        );
+        return MMono::empty(midi);
+    };
+    let Some(body) = compile_voice_bodies(1, body, sample_rate, ctx).pop() else {
+        return MMono::empty(midi);
+    };
+    if body.is_empty() {
+        log::warn!("Empty mmono voice body; compiling to a zero-output mmono.");
+        return MMono::empty(midi);
+    }
+    let mut mmono = MMono::new(body, midi, priority, glide, sample_rate);
+    mmono.set_filter(filter);
+    mmono
 }
 
-fn parse_voice_count(op: &str) -> Option<usize> {
-    op.split(':')
-        .nth(1)
-        .and_then(|n| n.parse::<usize>().ok())
-        .filter(|&n| n > 0)
+/// `@`-separated `chA[-B][,..]` channel sets and `LOW[-HIGH]` key ranges;
+/// qualifiers of the same kind combine, and a missing kind allows all.
+fn parse_midi_filter(qualifiers: &str) -> Option<MidiFilter> {
+    let mut channels = 0u16;
+    let mut notes = 0u128;
+    for qualifier in qualifiers.split('@') {
+        if let Some(set) = qualifier.strip_prefix("ch") {
+            for part in set.split(',') {
+                let (low, high) = part.split_once('-').unwrap_or((part, part));
+                let low = parse_midi_channel(low)??;
+                let high = parse_midi_channel(high)?.filter(|&high| high >= low)?;
+                for channel in low..=high {
+                    channels |= 1 << channel;
+                }
+            }
+        } else {
+            // The range dash follows an octave digit; `C-1` is a single note.
+            let split = qualifier
+                .char_indices()
+                .skip(1)
+                .find(|&(i, c)| c == '-' && qualifier[..i].ends_with(|c: char| c.is_ascii_digit()))
+                .map(|(i, _)| i);
+            let (low, high) = match split {
+                Some(i) => (&qualifier[..i], &qualifier[i + 1..]),
+                None => (qualifier, qualifier),
+            };
+            let low = parse_midi_note(low)?;
+            let high = parse_midi_note(high).filter(|&high| high >= low)?;
+            for note in low..=high {
+                notes |= 1 << note;
+            }
+        }
+    }
+    Some(MidiFilter::new(
+        if channels == 0 { u16::MAX } else { channels },
+        if notes == 0 { u128::MAX } else { notes },
+    ))
+}
+
+/// MIDI note number `0..127` or note name such as `C1`, `F#3` or `C-1`.
+fn parse_midi_note(token: &str) -> Option<u8> {
+    if let Ok(note) = token.parse::<u8>() {
+        return (note < 128).then_some(note);
+    }
+    // Lowercase note names are frequencies elsewhere; here both mean keys.
+    let mut chars = token.chars();
+    let name = chars.next()?.to_ascii_uppercase();
+    let note = parse_note_constant(&format!("{}{}", name, chars.as_str()))?;
+    (0.0..128.0).contains(&note).then_some(note as u8)
+}
+
+/// Compile `<quotation> uni:N[:WIDTH]`: N copies of the body sharing node ids,
+/// like `poly` voices, with optional stereo spread `WIDTH` in `0..1`.
+fn compile_unison(op: &str, body: &[TextOp], sample_rate: u32, ctx: &mut Context) -> Unison {
+    let Some(copies) = parse_voice_count(op.split(':').nth(1).unwrap_or("")) else {
+        log::warn!(
+            "Can't parse copy count in {}; compiling to a zero-output uni.",
+            op
+        );
+        return Unison::empty();
+    };
+    let width = op.split(':').nth(2).and_then(|width| {
+        let parsed = width
+            .parse::<Sample>()
+            .ok()
+            .filter(|width| (0.0..=1.0).contains(width));
+        if parsed.is_none() {
+            log::warn!("Can't parse width in {}; copies stay unpanned.", op);
+        }
+        parsed
+    });
+    let bodies = compile_voice_bodies(copies, body, sample_rate, ctx);
+    if bodies.first().is_none_or(|body| body.is_empty()) {
+        log::warn!("Empty uni body; compiling to a zero-output uni.");
+        return Unison::empty();
+    }
+    Unison::new(bodies, width)
+}
+
+fn parse_voice_count(count: &str) -> Option<usize> {
+    count.parse::<usize>().ok().filter(|&n| n > 0)
+}
+
+/// N in `poly:N,K`.
+fn parse_poly_voice_count(op: &str) -> Option<usize> {
+    let count = op.split(':').nth(1).unwrap_or("");
+    parse_voice_count(count.split_once(',').map_or(count, |(n, _)| n))
+}
+
+/// Latched values per voice for `poly:N,K` and value lanes for `poly:N:L`
+/// (one voice per lane on each edge, e.g. fed by `chord:`); 1 when absent.
+/// All `K * L` values plus ctl must fit on the stack. A trailing `:idle`
+/// (idle voice detection) is not a lane count.
+fn parse_poly_inputs(op: &str) -> (usize, usize) {
+    let op = op.strip_suffix(":idle").unwrap_or(op);
+    let fits = |n: usize| n > 0 && n < STACK_SIZE;
+    let values = match op.split(':').nth(1).and_then(|count| count.split_once(',')) {
+        None => 1,
+        Some((_, values)) => match values.parse::<usize>() {
+            Ok(values) if fits(values) => values,
+            _ => {
+                log::warn!(
+                    "Can't parse value count in {}; latching a single value.",
+                    op
+                );
+                1
+            }
+        },
+    };
+    let lanes = match op.split(':').nth(2) {
+        None => 1,
+        Some(lanes) => match lanes.parse::<usize>() {
+            Ok(lanes) if fits(values * lanes) => lanes,
+            _ => {
+                log::warn!("Can't parse lane count in {}; using a single lane.", op);
+                1
+            }
+        },
+    };
+    (values, lanes)
+}
+
+/// Optional smoothing time in seconds; 0 when absent.
+fn parse_midi_smoothing(arg: Option<&str>) -> Option<Sample> {
+    match arg {
+        None => Some(0.0),
+        Some(time) => time
+            .parse::<Sample>()
+            .ok()
+            .filter(|time| time.is_finite() && *time >= 0.0),
+    }
+}
+
+/// User-facing MIDI channel `1..16`, or `0` for any channel.
+fn parse_midi_channel(arg: &str) -> Option<Option<u8>> {
+    match arg.parse::<u8>().ok()? {
+        0 => Some(None),
+        channel @ 1..=16 => Some(Some(channel - 1)),
+        _ => None,
+    }
+}
+
+/// `cc:N[:CH[:SMOOTH]]` arguments.
+fn parse_midi_control(args: &[&str]) -> Option<(u8, Option<u8>, Sample)> {
+    let controller = args.first()?.parse::<u8>().ok().filter(|&n| n < 128)?;
+    let channel = match args.get(1) {
+        Some(channel) => parse_midi_channel(channel)?,
+        None => None,
+    };
+    let smoothing = parse_midi_smoothing(args.get(2).copied())?;
+    (args.len() <= 3).then_some((controller, channel, smoothing))
+}
+
+/// `bend[:RANGE[:SMOOTH]]` / `vbend[:RANGE[:SMOOTH]]` arguments.
+fn parse_midi_bend(args: &[&str], default_range: Sample) -> Option<(Sample, Sample)> {
+    let range = match args.first() {
+        Some(range) => range.parse::<Sample>().ok().filter(|x| x.is_finite())?,
+        None => default_range,
+    };
+    let smoothing = parse_midi_smoothing(args.get(1).copied())?;
+    (args.len() <= 2).then_some((range, smoothing))
 }
 
 fn compile_voice_bodies(
@@ -368,13 +682,17 @@ fn compile_voice_bodies(
     sample_rate: u32,
     ctx: &mut Context,
 ) -> Vec<Box<[Statement]>> {
-    (0..voices)
-        .map(|_| {
+    let outer = ctx.voice;
+    let bodies = (0..voices)
+        .map(|index| {
+            ctx.voice = Some((index, voices));
             let mut voice = Vec::new();
             compile_ops(body, sample_rate, ctx, &mut voice);
             voice.into_boxed_slice()
         })
-        .collect()
+        .collect();
+    ctx.voice = outer;
+    bodies
 }
 
 fn compile_segment(
@@ -463,6 +781,22 @@ fn compile_segment(
             "%" | "mod" => push_args!(id, Fn2, pure::modulo),
             "adsr" => push_args!(id, ADSR, sample_rate),
             "amp2db" | "a2db" => push_args!(id, Fn1, pure::amp2db),
+            "bar" => program.push(Statement {
+                id,
+                op: Box::new(TransportValue::bar(Arc::clone(&ctx.transport))) as Box<dyn Op>,
+            }),
+            "barphase" => program.push(Statement {
+                id,
+                op: Box::new(TransportValue::bar_phase(Arc::clone(&ctx.transport))) as Box<dyn Op>,
+            }),
+            "beat" => program.push(Statement {
+                id,
+                op: Box::new(TransportValue::beat(Arc::clone(&ctx.transport))) as Box<dyn Op>,
+            }),
+            "beatphase" => program.push(Statement {
+                id,
+                op: Box::new(TransportValue::beat_phase(Arc::clone(&ctx.transport))) as Box<dyn Op>,
+            }),
             "c" => push_args!(id, Osc, sample_rate, pure::cosine),
             "c'" => push_args!(id, Osc, sample_rate, pure::cosine_fast),
             "chance" => program.push(Statement {
@@ -484,6 +818,7 @@ fn compile_segment(
             "cosine'" => push_args!(id, OscPhase, sample_rate, pure::cosine_fast),
             "crush" => push_args!(id, Crush, sample_rate),
             "cycle" | "cy" => push_args!(id, Cycle, sample_rate),
+            "rcycle" | "rcy" => push_args!(id, ResetCycle, sample_rate),
             "db2amp" | "db2a" => push_args!(id, Fn1, pure::db2amp),
             "dm" | "dmetro" => push_args!(id, DMetro, sample_rate),
             "dmh" | "dmetro_hold" => push_args!(id, DMetroHold, sample_rate),
@@ -510,6 +845,10 @@ fn compile_segment(
             "m2f" | "midi2freq" | "#" => push_args!(id, Fn1, pure::midi2freq),
             "max" => push_args!(id, Fn2, pure::max),
             "mh" | "metro_hold" => push_args!(id, MetroHold, sample_rate),
+            "midistart" => program.push(Statement {
+                id,
+                op: Box::new(MidiStart::new(Arc::clone(&ctx.midi))) as Box<dyn Op>,
+            }),
             "min" => push_args!(id, Fn2, pure::min),
             "n" | "noise" | "whiteNoise" => program.push(Statement {
                 id,
@@ -603,6 +942,14 @@ fn compile_segment(
             "tquad" => push_args!(id, Transition, sample_rate, pure::quadratic_curve),
             "uniexp" => push_args!(id, Fn3, pure::uniexp),
             "unit" => push_args!(id, Fn1, pure::unit),
+            "vi" | "vn" => {
+                let (index, count) = ctx.voice.unwrap_or_else(|| {
+                    log::warn!("{} outside a voice body; using voice 0 of 1.", op);
+                    (0, 1)
+                });
+                let value = if op == "vi" { index } else { count };
+                push_args!(id, Constant, value as Sample)
+            }
             "w" => push_args!(id, Phasor, sample_rate),
             "wah" => push_args!(id, WahPedal, sample_rate),
             "width" => push!(id, Width),
@@ -799,12 +1146,14 @@ fn compile_segment(
                             }
                         },
                         "param" => match tokens.get(1) {
-                            Some(x) => match x.parse::<usize>() {
-                                Ok(n) => push_args!(id, Param, Arc::clone(&ctx.params[n])),
-                                Err(_) => {
-                                    log::warn!("Can't parse {} as param number", x);
+                            Some(x) => {
+                                match x.parse::<usize>().ok().and_then(|n| ctx.params.get(n)) {
+                                    Some(param) => push_args!(id, Param, Arc::clone(param)),
+                                    None => {
+                                        log::warn!("Can't parse {} as param number", x);
+                                    }
                                 }
-                            },
+                            }
                             None => {
                                 log::warn!("Missing param number parameter.");
                             }
@@ -859,8 +1208,96 @@ fn compile_segment(
                                 push!(id, Noop);
                             }
                         },
+                        "bend" | "cc" | "modwheel" | "vbend" | "vpress" | "vtimbre" => {
+                            let midi = Arc::clone(&ctx.midi);
+                            if tokens[0].starts_with('v') && ctx.voice.is_none() {
+                                log::warn!("{} outside a voice body always reads 0.", op);
+                            }
+                            let control = match tokens[0] {
+                                "bend" => {
+                                    parse_midi_bend(&tokens[1..], 2.0).map(|(range, smoothing)| {
+                                        MidiControl::bend(midi, range, smoothing, sample_rate)
+                                    })
+                                }
+                                // MPE's default per-note bend range.
+                                "vbend" => {
+                                    parse_midi_bend(&tokens[1..], 48.0).map(|(range, smoothing)| {
+                                        MidiControl::voice_bend(midi, range, smoothing, sample_rate)
+                                    })
+                                }
+                                "vpress" | "vtimbre" if tokens.len() <= 2 => {
+                                    parse_midi_smoothing(tokens.get(1).copied()).map(|smoothing| {
+                                        if tokens[0] == "vpress" {
+                                            MidiControl::voice_pressure(
+                                                midi,
+                                                smoothing,
+                                                sample_rate,
+                                            )
+                                        } else {
+                                            MidiControl::voice_timbre(midi, smoothing, sample_rate)
+                                        }
+                                    })
+                                }
+                                "vpress" | "vtimbre" => None,
+                                "cc" => parse_midi_control(&tokens[1..]).map(
+                                    |(controller, channel, smoothing)| {
+                                        MidiControl::control(
+                                            midi,
+                                            controller,
+                                            channel,
+                                            smoothing,
+                                            sample_rate,
+                                        )
+                                    },
+                                ),
+                                _ => {
+                                    parse_midi_smoothing(tokens.get(1).copied()).map(|smoothing| {
+                                        MidiControl::control(
+                                            midi,
+                                            MOD_WHEEL,
+                                            None,
+                                            smoothing,
+                                            sample_rate,
+                                        )
+                                    })
+                                }
+                            };
+                            match control {
+                                Some(control) => program.push(Statement {
+                                    id,
+                                    op: Box::new(control) as Box<dyn Op>,
+                                }),
+                                None => {
+                                    log::warn!("Invalid MIDI control: {}", op);
+                                    push_args!(id, Constant, 0.0);
+                                }
+                            }
+                        }
+                        "markov" => {
+                            let table = tokens.get(1).copied().unwrap_or("");
+                            program.push(Statement {
+                                id,
+                                op: Box::new(Markov::with_seed(table, ctx.next_rng_seed()))
+                                    as Box<dyn Op>,
+                            });
+                        }
+                        "ca" | "catrig" => {
+                            let spec = op.split_once(':').map_or("", |(_, spec)| spec);
+                            let seed = ctx.next_rng_seed();
+                            let automaton = if tokens[0] == "ca" {
+                                CellularAutomaton::gate(spec, seed)
+                            } else {
+                                CellularAutomaton::trigger(spec, seed)
+                            };
+                            program.push(Statement {
+                                id,
+                                op: Box::new(automaton) as Box<dyn Op>,
+                            });
+                        }
+                        // Pattern readers take the whole remainder: `seq:` step records
+                        // and the `:swing:R` suffix contain `:` themselves.
                         "pat" => {
-                            let pattern = tokens.get(1).copied().unwrap_or("");
+                            let pattern = op.split_once(':').map_or("", |(_, pattern)| pattern);
                             program.push(Statement {
                                 id,
                                 op: Box::new(PatternValue::with_seed(
@@ -869,8 +1306,18 @@ fn compile_segment(
                                 )) as Box<dyn Op>,
                             });
                         }
+                        "chord" => {
+                            let pattern = op.split_once(':').map_or("", |(_, pattern)| pattern);
+                            program.push(Statement {
+                                id,
+                                op: Box::new(PatternChord::with_seed(
+                                    pattern,
+                                    ctx.next_rng_seed().unwrap_or(0),
+                                )) as Box<dyn Op>,
+                            });
+                        }
                         "gate" => {
-                            let pattern = tokens.get(1).copied().unwrap_or("");
+                            let pattern = op.split_once(':').map_or("", |(_, pattern)| pattern);
                             program.push(Statement {
                                 id,
                                 op: Box::new(PatternGate::with_seed(
@@ -880,7 +1327,7 @@ fn compile_segment(
                             });
                         }
                         "trig" => {
-                            let pattern = tokens.get(1).copied().unwrap_or("");
+                            let pattern = op.split_once(':').map_or("", |(_, pattern)| pattern);
                             program.push(Statement {
                                 id,
                                 op: Box::new(PatternTrigger::with_seed(
@@ -890,7 +1337,7 @@ fn compile_segment(
                             });
                         }
                         "cpat" => {
-                            let pattern = tokens.get(1).copied().unwrap_or("");
+                            let pattern = op.split_once(':').map_or("", |(_, pattern)| pattern);
                             program.push(Statement {
                                 id,
                                 op: Box::new(ClockedPatternValue::with_seed(
@@ -901,7 +1348,7 @@ fn compile_segment(
                             });
                         }
                         "cgate" => {
-                            let pattern = tokens.get(1).copied().unwrap_or("");
+                            let pattern = op.split_once(':').map_or("", |(_, pattern)| pattern);
                             program.push(Statement {
                                 id,
                                 op: Box::new(ClockedPatternGate::with_seed(
@@ -912,7 +1359,7 @@ fn compile_segment(
                             });
                         }
                         "ctrig" => {
-                            let pattern = tokens.get(1).copied().unwrap_or("");
+                            let pattern = op.split_once(':').map_or("", |(_, pattern)| pattern);
                             program.push(Statement {
                                 id,
                                 op: Box::new(ClockedPatternTrigger::with_seed(
@@ -922,11 +1369,131 @@ fn compile_segment(
                                 )) as Box<dyn Op>,
                             });
                         }
-                        "poly" => {
+                        "seq" => {
+                            let pattern = op.split_once(':').map_or("", |(_, pattern)| pattern);
+                            program.push(Statement {
+                                id,
+                                op: Box::new(PatternSeq::with_seed(
+                                    pattern,
+                                    ctx.next_rng_seed().unwrap_or(0),
+                                )) as Box<dyn Op>,
+                            });
+                        }
+                        "cseq" => {
+                            let pattern = op.split_once(':').map_or("", |(_, pattern)| pattern);
+                            program.push(Statement {
+                                id,
+                                op: Box::new(ClockedPatternSeq::with_seed(
+                                    sample_rate,
+                                    pattern,
+                                    ctx.next_rng_seed().unwrap_or(0),
+                                )) as Box<dyn Op>,
+                            });
+                        }
+                        "poly" | "polyx" => {
                             log::warn!(
-                                "poly without a preceding quotation; compiling to a zero-voice poly."
+                                "{} without a preceding quotation; compiling to a zero-voice poly.",
+                                tokens[0]
                             );
-                            push_args!(id, Poly, Vec::new());
+                            let (values, lanes) = parse_poly_inputs(&op);
+                            let mut poly = Poly::empty_with_inputs(values, lanes);
+                            if tokens[0] == "polyx" {
+                                poly.set_separate_outputs(
+                                    parse_poly_voice_count(&op).map(|n| n.min(STACK_SIZE)),
+                                );
+                            }
+                            program.push(Statement {
+                                id,
+                                op: Box::new(poly) as Box<dyn Op>,
+                            });
+                        }
+                        "uni" => {
+                            log::warn!(
+                                "uni without a preceding quotation; compiling to a zero-output uni."
+                            );
+                            program.push(Statement {
+                                id,
+                                op: Box::new(Unison::empty()) as Box<dyn Op>,
+                            });
+                        }
+                        "midiout" => {
+                            let channel = tokens
+                                .get(1)
+                                .and_then(|channel| parse_midi_channel(channel))
+                                .flatten()
+                                .filter(|_| tokens.len() == 2);
+                            let note_out = match channel {
+                                Some(channel) => {
+                                    MidiNoteOut::new(Arc::clone(&ctx.midi_out), channel)
+                                }
+                                None => {
+                                    log::warn!("Invalid MIDI output channel: {}", op);
+                                    MidiNoteOut::muted()
+                                }
+                            };
+                            program.push(Statement {
+                                id,
+                                op: Box::new(note_out) as Box<dyn Op>,
+                            });
+                        }
+                        "ccout" => {
+                            let controller = tokens
+                                .get(1)
+                                .and_then(|n| n.parse::<u8>().ok())
+                                .filter(|&n| n < 128);
+                            // Channel 1 unless given.
+                            let channel = match tokens.get(2) {
+                                Some(channel) => parse_midi_channel(channel).flatten(),
+                                None => Some(0),
+                            };
+                            let control_out = match (controller, channel) {
+                                (Some(controller), Some(channel)) if tokens.len() <= 3 => {
+                                    MidiControlOut::new(
+                                        Arc::clone(&ctx.midi_out),
+                                        controller,
+                                        channel,
+                                        sample_rate,
+                                    )
+                                }
+                                _ => {
+                                    log::warn!("Invalid MIDI control output: {}", op);
+                                    MidiControlOut::muted()
+                                }
+                            };
+                            program.push(Statement {
+                                id,
+                                op: Box::new(control_out) as Box<dyn Op>,
+                            });
+                        }
+                        "midicps" => {
+                            // One cycle per 4/4 bar by default.
+                            let pulses = match tokens.get(1) {
+                                Some(pulses) => pulses.parse::<u32>().ok().filter(|&n| n > 0),
+                                None => Some(4 * MIDI_CLOCK_PPQN),
+                            };
+                            match pulses {
+                                Some(pulses) if tokens.len() <= 2 => program.push(Statement {
+                                    id,
+                                    op: Box::new(MidiCps::new(
+                                        Arc::clone(&ctx.midi),
+                                        pulses,
+                                        sample_rate,
+                                    )) as Box<dyn Op>,
+                                }),
+                                _ => {
+                                    log::warn!("Invalid MIDI clock pulses per cycle: {}", op);
+                                    push_args!(id, Constant, 0.0);
+                                }
+                            }
+                        }
+                        name if name == "mmono" || name.starts_with("mmono@") => {
+                            log::warn!(
+                                "mmono without a preceding quotation; compiling to a zero-output mmono."
+                            );
+                            program.push(Statement {
+                                id,
+                                op: Box::new(MMono::empty(Arc::clone(&ctx.midi))) as Box<dyn Op>,
+                            });
                         }
                         "mpoly" => {
                             log::warn!(
@@ -1882,6 +2449,50 @@ mod tests {
         let _ = run_frames(&[op(1, "noise")], 100, 1);
     }
 
+    #[test]
+    fn transport_directives_set_tempo_and_position_survives_recompiles() {
+        let mut context = Context::new();
+        let mut vm = audio_vm::VM::new();
+        vm.set_xfade_duration(0.0);
+        vm.set_declick_duration(0.0);
+        let ops = [op(1, "bpm:60"), op(2, "meter:3"), op(3, "barphase")];
+        vm.load_program(compile_program(&ops, 4, &mut context));
+        vm.play();
+        let mut bar_phase = Vec::new();
+        for _ in 0..6 {
+            bar_phase.push(vm.next_frame()[0]);
+            context.transport.advance(0.25);
+        }
+        assert_eq!(
+            bar_phase,
+            vec![0.0, 1.0 / 12.0, 2.0 / 12.0, 0.25, 4.0 / 12.0, 5.0 / 12.0]
+        );
+
+        // Position is 1.5 beats: beat 1 of bar 0 in 3/4.
+        vm.load_program(compile_program(
+            &[
+                op(4, "beat"),
+                op(5, "bar"),
+                op(6, "10"),
+                op(7, "*"),
+                op(8, "+"),
+            ],
+            4,
+            &mut context,
+        ));
+        assert_eq!(context.transport.bpm(), 60.0);
+        assert_eq!(vm.next_frame(), [1.0, 1.0]);
+        for _ in 0..10 {
+            context.transport.advance(0.25);
+        }
+        // 4 beats: beat 1 of bar 1.
+        assert_eq!(vm.next_frame(), [11.0, 11.0]);
+
+        compile_program(&[op(1, "bpm:fast"), op(2, "meter:0")], 4, &mut context);
+        assert_eq!(context.transport.bpm(), 60.0);
+        assert_eq!(context.transport.meter(), 3.0);
+    }
+
     #[test]
     fn new_random_and_scale_ops_compile_and_obey_seed() {
         let rnd_ops = [op(1, "seed:42"), op(2, "1"), op(3, "rnd")];
@@ -1900,6 +2511,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn markov_compiles_and_obeys_seed() {
+        let ops = |seed| {
+            [
+                op(1, seed),
+                op(2, "50"),
+                op(3, "m"),
+                op(4, "markov:60>64,67;64>60,67;67>60,64"),
+            ]
+        };
+        let a = run_frames(&ops("seed:42"), 1000, 1000);
+        assert_eq!(a, run_frames(&ops("seed:42"), 1000, 1000));
+        assert_ne!(a, run_frames(&ops("seed:43"), 1000, 1000));
+        assert!(a.iter().all(|frame| [60.0, 64.0, 67.0].contains(&frame[0])));
+        assert_eq!(
+            run_once(&[op(1, "1"), op(2, "markov:60")], &mut Context::new()),
+            [0.0, 0.0]
+        );
+    }
+
     #[test]
     fn seed_directive_makes_pattern_random_choice_reproducible_and_consumes_no_stack() {
         let ops = [
@@ -2150,6 +2781,234 @@ mod tests {
         );
     }
 
+    #[test]
+    fn seq_keeps_colons_in_step_records() {
+        let expected = vec![0.5, 0.5, 0.0, 0.5, 0.0, 1.0, 1.0, 1.0];
+        assert_eq!(
+            channel(
+                &run_frames(&[op(1, "1"), op(2, "cseq:x:0.5:r2,x")], 8, 8),
+                0
+            ),
+            expected
+        );
+        assert_eq!(
+            channel(
+                &run_frames(&[op(1, "1"), op(2, "cy"), op(3, "seq:x:0.5:r2,x")], 8, 8),
+                0
+            ),
+            expected
+        );
+    }
+
+    #[test]
+    fn pattern_readers_accept_swing_suffix() {
+        assert_eq!(
+            channel(
+                &run_frames(&[op(1, "1"), op(2, "cpat:1,2:swing:0.5")], 8, 8),
+                0
+            ),
+            vec![1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 2.0, 2.0]
+        );
+    }
+
+    #[test]
+    fn cellular_automaton_steps_on_cycle_wraps() {
+        assert_eq!(
+            channel(
+                &run_frames(&[op(1, "1"), op(2, "cycle"), op(3, "ca:90:4")], 8, 16),
+                0
+            ),
+            vec![
+                0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, // ..x.
+                0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 1.0, // .x.x
+            ]
+        );
+    }
+
+    #[test]
+    fn voice_index_and_count_resolve_per_voice_body() {
+        let mut context = Context::new();
+
+        // Every voice runs: 0 + 1 + 2, and 3 * 3.
+        for (word, sum) in [("vi", 3.0), ("vn", 9.0)] {
+            assert_eq!(
+                run_once(
+                    &[
+                        op(1, "0"),
+                        op(2, "1"),
+                        op(3, "["),
+                        op(4, "pop"),
+                        op(5, word),
+                        op(6, "+"),
+                        op(7, "]"),
+                        op(8, "poly:3"),
+                    ],
+                    &mut context
+                ),
+                [sum, sum],
+                "{word}"
+            );
+        }
+        // Top level falls back to voice 0 of 1.
+        assert_eq!(
+            run_once(&[op(1, "vi"), op(2, "vn"), op(3, "+")], &mut context),
+            [1.0, 1.0]
+        );
+    }
+
+    #[test]
+    fn compile_program_runs_unison_copies_on_the_same_input() {
+        let mut context = Context::new();
+
+        // Copies see `[10, spread]` with spreads -1, 0, 1; `vi` adds 0, 1, 2.
+        assert_eq!(
+            run_once(
+                &[
+                    op(1, "10"),
+                    op(2, "["),
+                    op(3, "+"),
+                    op(4, "vi"),
+                    op(5, "+"),
+                    op(6, "]"),
+                    op(7, "uni:3"),
+                ],
+                &mut context
+            ),
+            [33.0, 33.0]
+        );
+        // Without a quotation it consumes the input and pushes silence.
+        assert_eq!(
+            run_once(
+                &[op(1, "5"), op(2, "10"), op(3, "uni:3"), op(4, "+")],
+                &mut context
+            ),
+            [5.0, 5.0]
+        );
+    }
+
+    #[test]
+    fn compile_program_pushes_separate_polyx_voice_frames() {
+        let mut context = Context::new();
+
+        // Voice 0 latches 5 with ctl 1, voice 1 is silent: frames 6 and 0,
+        // then `-` gives 6 - 0 and `polyx` without a quotation keeps shape.
+        assert_eq!(
+            run_once(
+                &[
+                    op(1, "5"),
+                    op(2, "1"),
+                    op(3, "["),
+                    op(4, "+"),
+                    op(5, "]"),
+                    op(6, "polyx:2"),
+                    op(7, "-"),
+                ],
+                &mut context
+            ),
+            [6.0, 6.0]
+        );
+        assert_eq!(
+            run_once(
+                &[
+                    op(1, "3"),
+                    op(2, "5"),
+                    op(3, "1"),
+                    op(4, "polyx:2"),
+                    op(5, "+"),
+                    op(6, "+"),
+                ],
+                &mut context
+            ),
+            [3.0, 3.0]
+        );
+    }
+
+    #[test]
+    fn compile_program_accepts_idle_detection_suffix() {
+        let mut context = Context::new();
+
+        // `:idle` is not a lane count: one value and ctl, (5 + 1) + 0.
+        for poly in ["poly:2:idle", "poly:2:1:idle"] {
+            assert_eq!(
+                run_once(
+                    &[
+                        op(1, "5"),
+                        op(2, "1"),
+                        op(3, "["),
+                        op(4, "+"),
+                        op(5, "]"),
+                        op(6, poly),
+                    ],
+                    &mut context
+                ),
+                [6.0, 6.0],
+                "{poly}"
+            );
+        }
+    }
+
+    #[test]
+    fn compile_program_latches_several_values_per_poly_voice() {
+        let mut context = Context::new();
+
+        // `poly:2,2` seeds the voice with `[5, 3, ctl]`: (3 + 1) * 5.
+        assert_eq!(
+            run_once(
+                &[
+                    op(1, "5"),
+                    op(2, "3"),
+                    op(3, "1"),
+                    op(4, "["),
+                    op(5, "+"),
+                    op(6, "*"),
+                    op(7, "]"),
+                    op(8, "poly:2,2"),
+                ],
+                &mut context
+            ),
+            [20.0, 20.0]
+        );
+        // Without a quotation it still consumes both values and ctl.
+        assert_eq!(
+            run_once(
+                &[
+                    op(1, "7"),
+                    op(2, "5"),
+                    op(3, "3"),
+                    op(4, "1"),
+                    op(5, "poly:2,2"),
+                    op(6, "+")
+                ],
+                &mut context
+            ),
+            [7.0, 7.0]
+        );
+    }
+
+    #[test]
+    fn compile_program_runs_chord_lanes_into_poly_voices() {
+        let mut context = Context::new();
+
+        // `chord:` pushes three lanes; `poly:4:3` starts one voice per tone
+        // on the first edge and each body adds its tone and the ctl:
+        // (60 + 1) + (64 + 1) + (67 + 1).
+        assert_eq!(
+            run_once(
+                &[
+                    op(1, "0"),
+                    op(2, "chord:[60+64+67],62+65+69"),
+                    op(3, "1"),
+                    op(4, "["),
+                    op(5, "+"),
+                    op(6, "]"),
+                    op(7, "poly:4:3"),
+                ],
+                &mut context
+            ),
+            [194.0, 194.0]
+        );
+    }
+
     #[test]
     fn compile_program_runs_mpoly_quotation_from_midi_events() {
         let mut context = Context::new();
@@ -2171,6 +3030,229 @@ mod tests {
         assert_eq!(vm.next_frame(), [124.25, 124.25]);
     }
 
+    #[test]
+    fn midi_control_ops_read_latched_controllers() {
+        let mut context = Context::new();
+        context.midi.set_events(&[
+            MidiEvent::control_change(1, 74, 0.5),
+            MidiEvent::control_change(0, MOD_WHEEL, 0.25),
+            MidiEvent::pitch_bend(0, -0.5),
+        ]);
+        assert_eq!(run_once(&[op(1, "cc:74:2")], &mut context), [0.5, 0.5]);
+        assert_eq!(run_once(&[op(1, "cc:74:1")], &mut context), [0.0, 0.0]);
+        assert_eq!(run_once(&[op(1, "modwheel")], &mut context), [0.25, 0.25]);
+        assert_eq!(run_once(&[op(1, "bend:12")], &mut context), [-6.0, -6.0]);
+        assert_eq!(run_once(&[op(1, "bend")], &mut context), [-1.0, -1.0]);
+        // Invalid forms push a single 0.
+        for invalid in ["cc", "cc:128", "cc:1:17", "cc:1:0:-1", "bend:x"] {
+            assert_eq!(
+                run_once(&[op(1, "3"), op(2, invalid), op(3, "+")], &mut context),
+                [3.0, 3.0],
+                "{invalid}"
+            );
+        }
+    }
+
+    #[test]
+    fn mpe_mpoly_voices_read_per_note_expression() {
+        let mut context = Context::new();
+        let midi = Arc::clone(&context.midi);
+        let mut vm = audio_vm::VM::new();
+        vm.set_xfade_duration(0.0);
+        vm.load_program(compile_program(
+            &[
+                op(1, "["),
+                op(2, "pop"),
+                op(3, "pop"),
+                op(4, "vbend"),
+                op(5, "vtimbre"),
+                op(6, "+"),
+                op(7, "vpress:x"),
+                op(8, "+"),
+                op(9, "]"),
+                op(10, "mpoly:2:mpe"),
+            ],
+            100,
+            &mut context,
+        ));
+        vm.play();
+
+        midi.set_events(&[
+            MidiEvent::note_on(1, 60, 1.0),
+            MidiEvent::pitch_bend(1, 0.5),
+            MidiEvent::control_change(1, 74, 0.25),
+            MidiEvent::pitch_bend(2, 1.0),
+        ]);
+        assert_eq!(vm.next_frame(), [24.25, 24.25]);
+    }
+
+    #[test]
+    fn mpoly_qualifiers_split_one_keyboard_between_bodies() {
+        let mut context = Context::new();
+        let midi = Arc::clone(&context.midi);
+        let mut vm = audio_vm::VM::new();
+        vm.set_xfade_duration(0.0);
+        vm.load_program(compile_program(
+            &[
+                op(1, "["),
+                op(2, "pop"),
+                op(3, "]"),
+                op(4, "mpoly:2@ch1@C1-B2"),
+                op(5, "["),
+                op(6, "pop"),
+                op(7, "1000"),
+                op(8, "+"),
+                op(9, "]"),
+                op(10, "mpoly:2@ch1,3@c3-127"),
+                op(11, "+"),
+            ],
+            100,
+            &mut context,
+        ));
+        vm.play();
+
+        midi.set_events(&[
+            MidiEvent::note_on(0, 36, 1.0),
+            MidiEvent::note_on(2, 60, 1.0),
+            MidiEvent::note_on(1, 40, 1.0),
+            MidiEvent::note_on(0, 20, 1.0),
+        ]);
+        assert_eq!(vm.next_frame(), [1096.0, 1096.0]);
+
+        for (qualified, valid) in [
+            ("mpoly:1@ch0", false),
+            ("mpoly:1@ch2-1", false),
+            ("mpoly:1@B2-C1", false),
+            ("mpoly:1@C-1-G9", true),
+            ("mpoly:1@x", false),
+        ] {
+            assert_eq!(
+                parse_midi_filter(qualified.split_once('@').unwrap().1).is_some(),
+                valid,
+                "{qualified}"
+            );
+        }
+    }
+
+    #[test]
+    fn compile_program_runs_mmono_quotation_legato() {
+        let mut context = Context::new();
+        let midi = Arc::clone(&context.midi);
+        let mut vm = audio_vm::VM::new();
+        vm.set_xfade_duration(0.0);
+        vm.load_program(compile_program(
+            &[
+                op(1, "["),
+                op(2, "+"),
+                op(3, "]"),
+                op(4, "mmono:high@C3-C9"),
+            ],
+            100,
+            &mut context,
+        ));
+        vm.play();
+
+        midi.set_events(&[MidiEvent::note_on(0, 60, 0.5)]);
+        assert_eq!(vm.next_frame(), [60.5, 60.5]);
+        midi.set_events(&[
+            MidiEvent::note_on(0, 55, 1.0),
+            MidiEvent::note_on(0, 40, 1.0),
+        ]);
+        assert_eq!(vm.next_frame(), [60.5, 60.5]);
+        midi.set_events(&[MidiEvent::note_off(0, 60)]);
+        assert_eq!(vm.next_frame(), [55.5, 55.5]);
+
+        for invalid in ["mmono:loud", "mmono:low:-1", "mmono:low:0:1", "mmono@ch17"] {
+            assert_eq!(
+                run_once(
+                    &[op(1, "["), op(2, "1"), op(3, "]"), op(4, invalid)],
+                    &mut context
+                ),
+                [0.0, 0.0],
+                "{invalid}"
+            );
+        }
+
+        // Without a quotation every form pushes one silent frame.
+        for bare in ["mmono", "mmono:low", "mmono@ch1", "mmono:high@C3-C9"] {
+            assert_eq!(
+                run_once(&[op(1, "7"), op(2, bare)], &mut context),
+                [0.0, 0.0],
+                "{bare}"
+            );
+        }
+    }
+
+    #[test]
+    fn midi_clock_drives_cycles_that_restart_on_start() {
+        let mut context = Context::new();
+        let midi = Arc::clone(&context.midi);
+        let mut vm = audio_vm::VM::new();
+        vm.set_xfade_duration(0.0);
+        vm.load_program(compile_program(
+            &[op(1, "midicps:2"), op(2, "midistart"), op(3, "rcycle")],
+            100,
+            &mut context,
+        ));
+        vm.play();
+
+        let clock = MidiEvent::realtime(MidiEventKind::Clock);
+        let mut phases = Vec::new();
+        for frame in 0..30 {
+            let events: &[MidiEvent] = match frame {
+                0 | 10 => &[clock],
+                // Pulses every 10 frames at 100 Hz, 2 per cycle: 5 cps.
+                20 => &[MidiEvent::realtime(MidiEventKind::Start), clock],
+                _ => &[],
+            };
+            midi.set_events(events);
+            phases.push(vm.next_frame()[0]);
+        }
+        assert!((phases[19] - 0.45).abs() < 1e-9, "{}", phases[19]);
+        assert_eq!(phases[20], 0.0);
+        assert!((phases[21] - 0.05).abs() < 1e-9, "{}", phases[21]);
+    }
+
+    #[test]
+    fn midi_output_ops_queue_events_and_consume_their_inputs() {
+        let mut context = Context::new();
+        let midi_out = Arc::clone(&context.midi_out);
+        assert_eq!(
+            run_once(
+                &[
+                    op(1, "3"),
+                    op(2, "60"),
+                    op(3, "1"),
+                    op(4, "midiout:10"),
+                    op(5, "0.5"),
+                    op(6, "ccout:7"),
+                ],
+                &mut context
+            ),
+            [3.0, 3.0]
+        );
+        let mut events = [MidiEvent::note_off(0, 0); MAX_MIDI_EVENTS_PER_FRAME];
+        let len = midi_out.drain(&mut events);
+        // The program was dropped with its note held, so its release comes
+        // with the same drain, ahead of the queued events.
+        assert_eq!(
+            events[..len],
+            [
+                MidiEvent::note_off(9, 60),
+                MidiEvent::note_on(9, 60, 1.0),
+                MidiEvent::control_change(0, 7, 64.0 / 127.0)
+            ]
+        );
+        // Invalid forms still consume their inputs but send nothing.
+        for (invalid, inputs) in [("midiout", 2), ("midiout:0", 2), ("ccout:128", 1)] {
+            let mut ops = vec![op(1, "3")];
+            ops.extend((0..inputs).map(|i| op(2 + i, "1")));
+            ops.push(op(9, invalid));
+            assert_eq!(run_once(&ops, &mut context), [3.0, 3.0], "{invalid}");
+        }
+        assert_eq!(midi_out.drain(&mut events), 0);
+    }
+
     #[test]
     fn compile_program_forgives_invalid_mpoly_forms_without_consuming_stack() {
         let mut context = Context::new();
@@ -1,25 +1,34 @@
+use crate::{midi_timing::FrameScheduler, record::RecordFeed};
 use anyhow::Result;
-use audio_ops::{MAX_MIDI_EVENTS_PER_FRAME, MidiEvent, MidiFrameEvents, pure::clip};
+use audio_ops::{
+    MAX_MIDI_EVENTS_PER_FRAME, MidiEvent, MidiEventKind, MidiFrameEvents, MidiOutEvents, Transport,
+    pure::clip,
+};
 use audio_vm::{CHANNELS, Program, Sample, VM};
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use crossbeam_channel::{Receiver, Sender};
 use rtrb::{Consumer, Producer, PushError};
-use std::sync::Arc;
+use std::{sync::Arc, time::Instant};
 
 pub enum Command {
     Play(bool),
     LoadProgram(Program),
     Monitor(u64),
     PatternMonitors(Vec<u64>),
+    ResetTransport,
 }
 
 pub fn main(
     vm: VM,
-    producer: Producer<Sample>,
+    record: RecordFeed,
     command_rx: Consumer<Command>,
     garbage_tx: Producer<Program>,
     midi_rx: Option<Consumer<MidiEvent>>,
+    midi_epoch: Instant,
     midi_frame: Arc<MidiFrameEvents>,
+    midi_out: Arc<MidiOutEvents>,
+    midi_out_tx: Option<Producer<MidiEvent>>,
+    transport: Arc<Transport>,
     rx: Receiver<()>,
     tx: Sender<u32>,
 ) -> Result<()> {
@@ -45,33 +54,45 @@ pub fn main(
             &device,
             config.into(),
             vm,
-            producer,
+            record,
             command_rx,
             garbage_tx,
             midi_rx,
+            FrameScheduler::new(midi_epoch, sample_rate),
             midi_frame,
+            midi_out,
+            midi_out_tx,
+            transport,
             rx,
         ),
         cpal::SampleFormat::I16 => run::<i16>(
             &device,
             config.into(),
             vm,
-            producer,
+            record,
             command_rx,
             garbage_tx,
             midi_rx,
+            FrameScheduler::new(midi_epoch, sample_rate),
             midi_frame,
+            midi_out,
+            midi_out_tx,
+            transport,
             rx,
         ),
         cpal::SampleFormat::U16 => run::<u16>(
             &device,
             config.into(),
             vm,
-            producer,
+            record,
             command_rx,
             garbage_tx,
             midi_rx,
+            FrameScheduler::new(midi_epoch, sample_rate),
             midi_frame,
+            midi_out,
+            midi_out_tx,
+            transport,
             rx,
         ),
         sample_format => Err(anyhow::anyhow!(
@@ -84,17 +105,22 @@ fn run<T>(
     device: &cpal::Device,
     config: cpal::StreamConfig,
     mut vm: VM,
-    mut producer: Producer<Sample>,
+    mut record: RecordFeed,
     mut command_rx: Consumer<Command>,
     mut garbage_tx: Producer<Program>,
     mut midi_rx: Option<Consumer<MidiEvent>>,
+    mut midi_scheduler: FrameScheduler,
     midi_frame: Arc<MidiFrameEvents>,
+    midi_out: Arc<MidiOutEvents>,
+    mut midi_out_tx: Option<Producer<MidiEvent>>,
+    transport: Arc<Transport>,
     rx: Receiver<()>,
 ) -> Result<()>
 where
     T: cpal::Sample + cpal::SizedSample + cpal::FromSample<f32>,
 {
     let channels = config.channels as usize;
+    let sample_period = (config.sample_rate as Sample).recip();
     let err_fn = |err| eprintln!("an error occurred on stream: {}", err);
     let stream = device.build_output_stream(
         config,
@@ -103,11 +129,16 @@ where
                 data,
                 channels,
                 &mut vm,
-                &mut producer,
+                &mut record,
                 &mut command_rx,
                 &mut garbage_tx,
                 midi_rx.as_mut(),
+                &mut midi_scheduler,
                 &midi_frame,
+                &midi_out,
+                midi_out_tx.as_mut(),
+                &transport,
+                sample_period,
             )
         },
         err_fn,
@@ -123,11 +154,16 @@ fn write_data<T>(
     output: &mut [T],
     channels: usize,
     vm: &mut VM,
-    producer: &mut Producer<Sample>,
+    record: &mut RecordFeed,
     command_rx: &mut Consumer<Command>,
     garbage_tx: &mut Producer<Program>,
     mut midi_rx: Option<&mut Consumer<MidiEvent>>,
+    midi_scheduler: &mut FrameScheduler,
     midi_frame: &MidiFrameEvents,
+    midi_out: &MidiOutEvents,
+    mut midi_out_tx: Option<&mut Producer<MidiEvent>>,
+    transport: &Transport,
+    sample_period: Sample,
 ) where
     T: cpal::Sample + cpal::SizedSample + cpal::FromSample<f32>,
 {
@@ -135,7 +171,11 @@ fn write_data<T>(
     while let Ok(command) = command_rx.pop() {
         match command {
             Command::Play(true) => vm.play(),
-            Command::Play(false) => vm.pause(),
+            Command::Play(false) => {
+                vm.pause();
+                // Paused ops can't send their note-offs.
+                midi_out.release_all();
+            }
             Command::LoadProgram(program) => {
                 let garbage = vm.load_program(program);
                 if let Err(PushError::Full(garbage)) = garbage_tx.push(garbage) {
@@ -145,26 +185,52 @@ fn write_data<T>(
             }
             Command::Monitor(id) => vm.set_monitor_id(id),
             Command::PatternMonitors(ids) => vm.set_pattern_monitor_ids(ids),
+            Command::ResetTransport => transport.reset(),
         }
     }
 
+    midi_scheduler.begin_buffer(output.len() / channels);
     let mut midi_events = [MidiEvent::note_off(0, 0); MAX_MIDI_EVENTS_PER_FRAME];
-    for frame in output.chunks_mut(channels) {
+    for (index, frame) in output.chunks_mut(channels).enumerate() {
         let mut midi_count = 0;
         if let Some(midi_rx) = midi_rx.as_deref_mut() {
+            // Events wait in the ring until the frame they were played on.
             while midi_count < midi_events.len() {
+                match midi_rx.peek() {
+                    Ok(event) if midi_scheduler.frame_offset(event.timestamp) <= index => {}
+                    _ => break,
+                }
                 let Ok(event) = midi_rx.pop() else {
                     break;
                 };
+                // An external sequencer's Start rewinds the transport too.
+                if event.kind == MidiEventKind::Start {
+                    transport.reset();
+                }
                 midi_events[midi_count] = event;
                 midi_count += 1;
             }
         }
+        for event in &midi_events[..midi_count] {
+            record.push_midi(*event);
+        }
         midi_frame.set_events(&midi_events[..midi_count]);
+        // The transport stands still while paused, like the ops reading it.
+        let running = vm.is_running();
         for (sample, &value) in frame.iter_mut().zip(vm.next_frame().iter()) {
             let value = clip(value);
             *sample = T::from_sample(value as f32);
-            producer.push(value).ok();
+            record.push_sample(value);
+        }
+        // Drained even without an output so the ops never see a full frame.
+        let count = midi_out.drain(&mut midi_events);
+        if let Some(midi_out_tx) = midi_out_tx.as_deref_mut() {
+            for event in &midi_events[..count] {
+                midi_out_tx.push(*event).ok();
+            }
+        }
+        if running {
+            transport.advance(sample_period);
         }
     }
 }
@@ -1,30 +1,44 @@
 use audio_program::{Context, TextOp, compile_program};
-use audio_vm::{CHANNELS, Frame, Program, Sample, VM};
+use audio_vm::{CHANNELS, Frame, Program, VM};
 use crossbeam_channel::{Receiver, Sender};
 use rkyv::{Archive, Deserialize as RkyvDeserialize, Serialize as RkyvSerialize};
 use rtrb::RingBuffer;
 use serde::{Deserialize, Serialize};
+use sound_garden_types::{MidiBinding, ParamMapping};
 use std::{
     sync::{Arc, atomic::Ordering},
-    time::Duration,
+    time::{Duration, Instant},
 };
 use thread_worker::Worker;
 
 mod audio;
 mod midi;
+mod midi_learn;
+mod midi_out;
+mod midi_timing;
 mod record;
 
 const CHANNEL_CAPACITY: usize = 64;
 /// It's about 500ms, should be more than enough for write cycle of ~10ms.
 const RECORD_BUFFER_CAPACITY: usize = 48000;
 const OSCILLOSCOPE_POLL_MS: u64 = 10;
+/// How often MIDI inputs are checked for unplugged and replugged devices.
+const MIDI_RESCAN_MS: u64 = 2000;
 
 #[derive(Clone, Debug, Default)]
 pub struct Options {
-    pub midi: MidiInputSelection,
+    /// One input per selection, merged into the same event stream.
+    pub midi: Vec<MidiPortSelection>,
+    pub midi_out: MidiPortSelection,
+    /// Receives the state of every MIDI input after each connection change.
+    pub midi_status: Option<Sender<Vec<MidiInputStatus>>>,
+    /// Receives each binding made by `Msg::MidiLearn`.
+    pub midi_learned: Option<Sender<MidiBinding>>,
 }
 
-pub use midi::{MidiInputSelection, list_inputs as list_midi_inputs};
+pub use midi::{MidiInputStatus, MidiPortSelection, list_inputs as list_midi_inputs};
+pub use midi_learn::param_mapping;
+pub use midi_out::list_outputs as list_midi_outputs;
 
 #[derive(Clone, Debug)]
 pub struct Monitor {
@@ -40,6 +54,16 @@ pub enum Msg {
     Monitor(u64),
     PatternMonitors(Vec<u64>),
     Oscilloscope(bool),
+    /// Set transport tempo in beats per minute.
+    Tempo(f64),
+    /// Set transport beats per bar.
+    Meter(u32),
+    /// Rewind transport to the first beat of bar 0.
+    ResetTransport,
+    /// Bind the next incoming control change to a param, or cancel learning.
+    MidiLearn(Option<ParamMapping>),
+    /// Replace every MIDI-learned binding, e.g. with the document's.
+    MidiBindings(Vec<MidiBinding>),
     Quit,
 }
 
@@ -53,25 +77,73 @@ pub fn run_with_options(rx: Receiver<Msg>, tx: Sender<Monitor>, options: Options
     let vm = VM::new();
     let monitor = vm.monitor();
     let pattern_monitor = vm.pattern_monitor();
-    let (producer, consumer) = RingBuffer::<Sample>::new(RECORD_BUFFER_CAPACITY);
+    let (record_feed, record_tap) = record::feed(RECORD_BUFFER_CAPACITY);
     let (mut command_tx, command_rx) = RingBuffer::<audio::Command>::new(CHANNEL_CAPACITY);
     let (garbage_tx, mut garbage_rx) = RingBuffer::<Program>::new(CHANNEL_CAPACITY);
     let mut ctx = Context::default();
     let midi_frame = Arc::clone(&ctx.midi);
-    let (midi_connection, midi_rx) = match midi::open_input(&options.midi) {
-        Ok(Some((connection, consumer, name))) => {
-            log::info!("Connected MIDI input: {name}");
-            (Some(connection), Some(consumer))
+    let transport = Arc::clone(&ctx.transport);
+    let midi_out = Arc::clone(&ctx.midi_out);
+    let midi_epoch = Instant::now();
+    let midi_learn = Arc::new(midi_learn::MidiLearn::new(
+        ctx.params.clone(),
+        options.midi_learned,
+    ));
+    let (midi_inputs, midi_rx) = if options.midi.is_empty() {
+        (None, None)
+    } else {
+        let (mut inputs, consumer) =
+            midi::MidiInputs::new(options.midi, Arc::clone(&midi_learn), midi_epoch);
+        let status_tx = options.midi_status;
+        let worker = Worker::spawn("MIDI inputs", 1, move |rx: Receiver<()>, _: Sender<()>| {
+            let mut first = true;
+            loop {
+                match inputs.rescan() {
+                    Ok(changed) => {
+                        if (changed || first)
+                            && let Some(status_tx) = &status_tx
+                        {
+                            status_tx.send(inputs.status()).ok();
+                        }
+                    }
+                    Err(err) if first => log::warn!("MIDI input unavailable: {err}"),
+                    Err(_) => {}
+                }
+                if first {
+                    for status in inputs
+                        .status()
+                        .iter()
+                        .filter(|status| status.port.is_none())
+                    {
+                        log::warn!(
+                            "No MIDI input matching {} yet, waiting for it to appear.",
+                            status.selection
+                        );
+                    }
+                }
+                first = false;
+                crossbeam_channel::select! {
+                    recv(rx) -> msg => if msg.is_err() { break },
+                    default(Duration::from_millis(MIDI_RESCAN_MS)) => {}
+                }
+            }
+        });
+        (Some(worker), Some(consumer))
+    };
+    let midi_out_tx = match midi_out::open_output(&options.midi_out) {
+        Ok(Some((producer, name))) => {
+            log::info!("Connected MIDI output: {name}");
+            Some(producer)
         }
         Ok(None) => {
-            if !matches!(options.midi, MidiInputSelection::None) {
-                log::warn!("No MIDI input connected.");
+            if !matches!(options.midi_out, MidiPortSelection::None) {
+                log::warn!("No MIDI output connected.");
             }
-            (None, None)
+            None
         }
         Err(err) => {
-            log::warn!("MIDI input unavailable: {err}");
-            (None, None)
+            log::warn!("MIDI output unavailable: {err}");
+            None
         }
     };
 
@@ -86,14 +158,25 @@ pub fn run_with_options(rx: Receiver<Msg>, tx: Sender<Monitor>, options: Options
 
     let player = Worker::spawn("Player", CHANNEL_CAPACITY, move |i, o| {
         audio::main(
-            vm, producer, command_rx, garbage_tx, midi_rx, midi_frame, i, o,
+            vm,
+            record_feed,
+            command_rx,
+            garbage_tx,
+            midi_rx,
+            midi_epoch,
+            midi_frame,
+            midi_out,
+            midi_out_tx,
+            transport,
+            i,
+            o,
         )
         .unwrap();
     });
     let sample_rate = player.receiver().recv().unwrap();
 
     let recorder = Worker::spawn("Recorder", CHANNEL_CAPACITY, move |i, o| {
-        record::main(sample_rate, consumer, i, o).unwrap();
+        record::main(sample_rate, record_tap, i, o).unwrap();
     });
 
     let scope = Worker::spawn(
@@ -130,7 +213,7 @@ pub fn run_with_options(rx: Receiver<Msg>, tx: Sender<Monitor>, options: Options
         },
     );
 
-    let _midi_connection = midi_connection;
+    let _midi_inputs = midi_inputs;
     for msg in rx {
         match msg {
             Msg::Play(x) => {
@@ -152,6 +235,13 @@ pub fn run_with_options(rx: Receiver<Msg>, tx: Sender<Monitor>, options: Options
             Msg::Oscilloscope(on) => {
                 scope.sender().send(on).ok();
             }
+            Msg::Tempo(bpm) => ctx.transport.set_bpm(bpm),
+            Msg::Meter(beats) => ctx.transport.set_meter(beats),
+            Msg::ResetTransport => {
+                command_tx.push(audio::Command::ResetTransport).ok();
+            }
+            Msg::MidiLearn(mapping) => midi_learn.arm(mapping),
+            Msg::MidiBindings(bindings) => midi_learn.set_bindings(bindings),
             Msg::Quit => {
                 break;
             }
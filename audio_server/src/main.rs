@@ -1,6 +1,7 @@
 use anyhow::Result;
 use audio_server::{
-    Message, MidiInputSelection, Monitor, Options, list_midi_inputs, run_with_options,
+    Message, MidiPortSelection, Monitor, Options, list_midi_inputs, list_midi_outputs,
+    run_with_options,
 };
 use clap::{Arg, Command, crate_authors, crate_description, crate_name, crate_version};
 use crossbeam_channel::{Receiver, Sender};
@@ -30,15 +31,31 @@ fn main() -> Result<()> {
                 .value_name("SCOPE_PORT")
                 .help("Port to send oscilloscope samples."),
         )
-        .arg(Arg::new("midi").long("midi").value_name("DEVICE").help(
-            "Connect a MIDI input: 'auto', device index, or case-insensitive name substring.",
-        ))
+        .arg(
+            Arg::new("midi")
+                .long("midi")
+                .value_name("DEVICE")
+                .action(clap::ArgAction::Append)
+                .help("Connect a MIDI input: 'auto', device index, or case-insensitive name substring. Repeat to merge several inputs."),
+        )
         .arg(
             Arg::new("list-midi")
                 .long("list-midi")
                 .action(clap::ArgAction::SetTrue)
                 .help("List available MIDI input devices and exit."),
         )
+        .arg(
+            Arg::new("midi-out")
+                .long("midi-out")
+                .value_name("DEVICE")
+                .help("Connect a MIDI output for midiout:/ccout: ops: 'auto', device index, or case-insensitive name substring."),
+        )
+        .arg(
+            Arg::new("list-midi-out")
+                .long("list-midi-out")
+                .action(clap::ArgAction::SetTrue)
+                .help("List available MIDI output devices and exit."),
+        )
         .get_matches();
 
     if matches.get_flag("list-midi") {
@@ -47,22 +64,38 @@ fn main() -> Result<()> {
         }
         return Ok(());
     }
+    if matches.get_flag("list-midi-out") {
+        for line in list_midi_outputs()? {
+            println!("{line}");
+        }
+        return Ok(());
+    }
 
     let scope_port = matches
         .get_one::<String>("scope-port")
         .and_then(|s| s.parse::<u16>().ok());
-    let midi = matches
-        .get_one::<String>("midi")
-        .map(|selection| {
-            if selection == "auto" {
-                MidiInputSelection::Auto
-            } else {
-                MidiInputSelection::Match(selection.clone())
-            }
-        })
-        .unwrap_or_default();
+    let port_selection = |selection: &String| {
+        if selection == "auto" {
+            MidiPortSelection::Auto
+        } else {
+            MidiPortSelection::Match(selection.clone())
+        }
+    };
+    let options = Options {
+        midi: matches
+            .get_many::<String>("midi")
+            .into_iter()
+            .flatten()
+            .map(port_selection)
+            .collect(),
+        midi_out: matches
+            .get_one::<String>("midi-out")
+            .map(port_selection)
+            .unwrap_or_default(),
+        ..Options::default()
+    };
     let worker = Worker::spawn("Synth", CHANNEL_CAPACITY, move |rx, tx| {
-        run_with_options(rx, tx, Options { midi });
+        run_with_options(rx, tx, options);
     });
 
     let oscilloscope = if let Some(port) = scope_port {
@@ -1,130 +1,247 @@
+use crate::{midi_learn::MidiLearn, midi_timing::MidiClock};
 use anyhow::{Result, anyhow};
-use audio_ops::{MIDI_EVENT_RING_CAPACITY, MidiEvent};
-use midir::{Ignore, MidiInput, MidiInputConnection, MidiInputPort};
-use rtrb::{Producer, RingBuffer};
+use audio_ops::{MIDI_EVENT_RING_CAPACITY, MidiEvent, MidiEventKind, decode_message};
+use midir::{Ignore, MidiIO, MidiInput, MidiInputConnection, MidiInputPort};
+use rtrb::{Consumer, Producer, RingBuffer};
+use std::{
+    sync::{Arc, Mutex},
+    time::Instant,
+};
 
-#[derive(Clone, Debug, Default)]
-pub enum MidiInputSelection {
+/// Which MIDI input or output port to connect.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum MidiPortSelection {
     #[default]
     None,
     Auto,
     Match(String),
 }
 
-pub struct MidiInputHandle {
-    #[allow(dead_code)]
-    connection: MidiInputConnection<()>,
+impl std::fmt::Display for MidiPortSelection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MidiPortSelection::None => write!(f, "none"),
+            MidiPortSelection::Auto => write!(f, "auto"),
+            MidiPortSelection::Match(query) => write!(f, "{query}"),
+        }
+    }
+}
+
+/// Connection state of one `--midi` selection.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MidiInputStatus {
+    pub selection: MidiPortSelection,
+    /// Name of the connected port, `None` while waiting for one.
+    pub port: Option<String>,
+}
+
+impl std::fmt::Display for MidiInputStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.port {
+            Some(port) => write!(f, "{port}"),
+            None => write!(f, "{} (offline)", self.selection),
+        }
+    }
+}
+
+struct ConnectedInput {
+    id: String,
+    name: String,
+    _connection: MidiInputConnection<()>,
+}
+
+/// Every `--midi` selection, each connected to its own port. All of them
+/// feed one ring to the audio thread, tagging events with the selection's
+/// index as their source. Control changes also drive MIDI-learned params.
+pub struct MidiInputs {
+    selections: Vec<MidiPortSelection>,
+    connected: Vec<Option<ConnectedInput>>,
+    /// Shared by the midir callback threads; never touched by audio.
+    producer: Arc<Mutex<Producer<MidiEvent>>>,
+    learn: Arc<MidiLearn>,
+    epoch: Instant,
 }
 
 pub fn list_inputs() -> Result<Vec<String>> {
-    let input = MidiInput::new("sound-garden-list-midi")?;
-    Ok(input
-        .ports()
+    Ok(list_ports(&MidiInput::new("sound-garden-list-midi")?))
+}
+
+pub(crate) fn list_ports<IO: MidiIO>(io: &IO) -> Vec<String> {
+    io.ports()
         .iter()
         .enumerate()
         .map(|(index, port)| {
-            let name = input
+            let name = io
                 .port_name(port)
                 .unwrap_or_else(|_| "<unknown>".to_string());
             format!("{index}: {name}")
         })
-        .collect())
+        .collect()
 }
 
-pub fn open_input(
-    selection: &MidiInputSelection,
-) -> Result<Option<(MidiInputHandle, rtrb::Consumer<MidiEvent>, String)>> {
-    match selection {
-        MidiInputSelection::None => Ok(None),
-        MidiInputSelection::Auto | MidiInputSelection::Match(_) => {
-            let mut input = MidiInput::new("sound-garden-midi")?;
-            input.ignore(Ignore::None);
-            let ports = input.ports();
-            let Some(port) = select_port(&input, &ports, selection)? else {
-                return Ok(None);
+impl MidiInputs {
+    /// Events are stamped on the clock started at `epoch`, shared with the
+    /// audio thread. Nothing is connected until the first `rescan`.
+    pub fn new(
+        selections: Vec<MidiPortSelection>,
+        learn: Arc<MidiLearn>,
+        epoch: Instant,
+    ) -> (Self, Consumer<MidiEvent>) {
+        let (producer, consumer) = RingBuffer::<MidiEvent>::new(MIDI_EVENT_RING_CAPACITY);
+        let connected = selections.iter().map(|_| None).collect();
+        let inputs = MidiInputs {
+            selections,
+            connected,
+            producer: Arc::new(Mutex::new(producer)),
+            learn,
+            epoch,
+        };
+        (inputs, consumer)
+    }
+
+    pub fn status(&self) -> Vec<MidiInputStatus> {
+        self.selections
+            .iter()
+            .zip(&self.connected)
+            .map(|(selection, connected)| MidiInputStatus {
+                selection: selection.clone(),
+                port: connected.as_ref().map(|input| input.name.clone()),
+            })
+            .collect()
+    }
+
+    /// Drop connections whose port went away and connect selections to
+    /// ports that (re)appeared. Returns whether any connection changed.
+    pub fn rescan(&mut self) -> Result<bool> {
+        let probe = MidiInput::new("sound-garden-midi-scan")?;
+        let ports = probe.ports();
+        let mut changed = false;
+        for slot in &mut self.connected {
+            if let Some(input) = slot
+                && !ports.iter().any(|port| port.id() == input.id)
+            {
+                log::warn!("MIDI input disconnected: {}", input.name);
+                *slot = None;
+                changed = true;
+            }
+        }
+        for source in 0..self.selections.len() {
+            if self.connected[source].is_some() {
+                continue;
+            }
+            let taken = |port: &MidiInputPort| {
+                self.connected
+                    .iter()
+                    .flatten()
+                    .any(|input| input.id == port.id())
+            };
+            // Indices refer to the full `--list-midi` listing; `auto` and
+            // names pick among ports no other selection holds.
+            let selection = &self.selections[source];
+            let by_index = matches!(
+                selection,
+                MidiPortSelection::Match(query) if query.parse::<usize>().is_ok()
+            );
+            let candidates = ports
+                .iter()
+                .filter(|port| by_index || !taken(port))
+                .cloned()
+                .collect::<Vec<_>>();
+            // A missing device is expected here: it may be plugged in later.
+            let Ok(Some(port)) = select_port(&probe, &candidates, selection, "input") else {
+                continue;
             };
-            let name = input
+            if taken(&port) {
+                continue;
+            }
+            let name = probe
                 .port_name(&port)
                 .unwrap_or_else(|_| "<unknown>".to_string());
-            let (producer, consumer) = RingBuffer::<MidiEvent>::new(MIDI_EVENT_RING_CAPACITY);
-            let connection = connect(input, &port, producer)?;
-            Ok(Some((MidiInputHandle { connection }, consumer, name)))
+            match self.connect(&port, source as u8) {
+                Ok(connection) => {
+                    log::info!("Connected MIDI input: {name}");
+                    self.connected[source] = Some(ConnectedInput {
+                        id: port.id(),
+                        name,
+                        _connection: connection,
+                    });
+                    changed = true;
+                }
+                Err(err) => log::warn!("MIDI input {name} unavailable: {err}"),
+            }
         }
+        Ok(changed)
+    }
+
+    fn connect(&self, port: &MidiInputPort, source: u8) -> Result<MidiInputConnection<()>> {
+        let mut input = MidiInput::new("sound-garden-midi")?;
+        input.ignore(Ignore::None);
+        let producer = Arc::clone(&self.producer);
+        let learn = Arc::clone(&self.learn);
+        let mut clock = MidiClock::new(self.epoch);
+        input
+            .connect(
+                port,
+                "sound-garden-midi-in",
+                move |timestamp, message, _| {
+                    if let Some(event) = decode_message(message) {
+                        if event.kind == MidiEventKind::ControlChange {
+                            learn.control_change(event.channel, event.note, event.velocity);
+                        }
+                        let event = event.at(clock.timestamp(timestamp)).with_source(source);
+                        if let Ok(mut producer) = producer.lock() {
+                            producer.push(event).ok();
+                        }
+                    }
+                },
+                (),
+            )
+            .map_err(|err| anyhow!("{err}"))
     }
 }
 
-fn select_port(
-    input: &MidiInput,
-    ports: &[MidiInputPort],
-    selection: &MidiInputSelection,
-) -> Result<Option<MidiInputPort>> {
+pub(crate) fn select_port<IO: MidiIO>(
+    io: &IO,
+    ports: &[IO::Port],
+    selection: &MidiPortSelection,
+    direction: &str,
+) -> Result<Option<IO::Port>> {
     match selection {
-        MidiInputSelection::None => Ok(None),
-        MidiInputSelection::Auto => Ok(ports.first().cloned()),
-        MidiInputSelection::Match(query) => {
+        MidiPortSelection::None => Ok(None),
+        MidiPortSelection::Auto => Ok(ports.first().cloned()),
+        MidiPortSelection::Match(query) => {
             if let Ok(index) = query.parse::<usize>() {
                 return Ok(ports.get(index).cloned());
             }
             let query = query.to_lowercase();
             for port in ports {
-                let name = input.port_name(port).unwrap_or_default();
+                let name = io.port_name(port).unwrap_or_default();
                 if name.to_lowercase().contains(&query) {
                     return Ok(Some(port.clone()));
                 }
             }
-            Err(anyhow!("No MIDI input matching {query:?}"))
-        }
-    }
-}
-
-fn connect(
-    input: MidiInput,
-    port: &MidiInputPort,
-    mut producer: Producer<MidiEvent>,
-) -> Result<MidiInputConnection<()>> {
-    Ok(input.connect(
-        port,
-        "sound-garden-midi-in",
-        move |_timestamp, message, _| {
-            if let Some(event) = decode_message(message) {
-                producer.push(event).ok();
-            }
-        },
-        (),
-    )?)
-}
-
-fn decode_message(message: &[u8]) -> Option<MidiEvent> {
-    let status = *message.first()?;
-    let channel = status & 0x0f;
-    match status & 0xf0 {
-        0x80 if message.len() >= 3 => Some(MidiEvent::note_off(channel, message[1])),
-        0x90 if message.len() >= 3 => {
-            let velocity = f64::from(message[2]) / 127.0;
-            if velocity > 0.0 {
-                Some(MidiEvent::note_on(channel, message[1], velocity))
-            } else {
-                Some(MidiEvent::note_off(channel, message[1]))
-            }
+            Err(anyhow!("No MIDI {direction} matching {query:?}"))
         }
-        _ => None,
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use audio_ops::MidiEventKind;
 
     #[test]
-    fn decodes_note_on_and_velocity_zero_as_off() {
+    fn input_status_shows_port_or_offline_selection() {
+        let status = |selection, port: Option<&str>| MidiInputStatus {
+            selection,
+            port: port.map(str::to_string),
+        };
         assert_eq!(
-            decode_message(&[0x91, 60, 64]),
-            Some(MidiEvent::note_on(1, 60, 64.0 / 127.0))
+            status(MidiPortSelection::Auto, Some("Keystation 49")).to_string(),
+            "Keystation 49"
         );
         assert_eq!(
-            decode_message(&[0x91, 60, 0]).map(|event| event.kind),
-            Some(MidiEventKind::NoteOff)
+            status(MidiPortSelection::Match("nano".to_string()), None).to_string(),
+            "nano (offline)"
         );
     }
 }
@@ -0,0 +1,154 @@
+//! MIDI learn: control changes bound to `param:N` slots. Bindings are applied
+//! on the MIDI callback threads, which write the parameter atomics directly,
+//! so a knob reaches the running program without a recompile.
+
+use audio_program::PARAMETERS;
+use audio_vm::{AtomicSample, Sample};
+use crossbeam_channel::Sender;
+use sound_garden_types::{MidiBinding, MidiCurve, ParamMapping, set_midi_binding};
+use std::sync::{Arc, Mutex, atomic::Ordering};
+
+/// Mapping of a `param:N[:MIN:MAX[:lin|exp]]` op, 0..1 linear by default.
+pub fn param_mapping(op: &str) -> Option<ParamMapping> {
+    let tokens = op.split(':').collect::<Vec<_>>();
+    if tokens.first() != Some(&"param") {
+        return None;
+    }
+    let param = tokens.get(1)?.parse::<usize>().ok()?;
+    if param >= PARAMETERS {
+        return None;
+    }
+    let (min, max) = match (tokens.get(2), tokens.get(3)) {
+        (Some(min), Some(max)) => (min.parse().ok()?, max.parse().ok()?),
+        (None, None) => (0.0, 1.0),
+        _ => return None,
+    };
+    let curve = match tokens.get(4).copied() {
+        None | Some("lin") => MidiCurve::Linear,
+        Some("exp") => MidiCurve::Exponential,
+        Some(_) => return None,
+    };
+    Some(ParamMapping {
+        param,
+        min,
+        max,
+        curve,
+    })
+}
+
+#[derive(Default)]
+struct LearnState {
+    bindings: Vec<MidiBinding>,
+    /// Mapping waiting for the next control change to bind it.
+    armed: Option<ParamMapping>,
+}
+
+pub struct MidiLearn {
+    params: [Arc<AtomicSample>; PARAMETERS],
+    /// Shared by the MIDI callback threads and the server loop.
+    state: Mutex<LearnState>,
+    learned_tx: Option<Sender<MidiBinding>>,
+}
+
+impl MidiLearn {
+    pub fn new(
+        params: [Arc<AtomicSample>; PARAMETERS],
+        learned_tx: Option<Sender<MidiBinding>>,
+    ) -> Self {
+        MidiLearn {
+            params,
+            state: Mutex::new(LearnState::default()),
+            learned_tx,
+        }
+    }
+
+    pub fn set_bindings(&self, bindings: Vec<MidiBinding>) {
+        if let Ok(mut state) = self.state.lock() {
+            state.bindings = bindings;
+        }
+    }
+
+    /// Bind the next control change from any input to `mapping`, or cancel.
+    pub fn arm(&self, mapping: Option<ParamMapping>) {
+        if let Ok(mut state) = self.state.lock() {
+            state.armed = mapping;
+        }
+    }
+
+    pub fn control_change(&self, channel: u8, controller: u8, value: Sample) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        if let Some(mapping) = state.armed.take() {
+            let binding = MidiBinding {
+                channel,
+                controller,
+                mapping,
+            };
+            set_midi_binding(&mut state.bindings, binding);
+            log::info!(
+                "Learned CC{controller} on channel {} for param:{}",
+                channel + 1,
+                mapping.param
+            );
+            if let Some(learned_tx) = &self.learned_tx {
+                learned_tx.send(binding).ok();
+            }
+        }
+        for binding in state
+            .bindings
+            .iter()
+            .filter(|binding| binding.channel == channel && binding.controller == controller)
+        {
+            if let Some(param) = self.params.get(binding.mapping.param) {
+                param.store(binding.mapping.value(value).to_bits(), Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn param(learn: &MidiLearn, index: usize) -> Sample {
+        Sample::from_bits(learn.params[index].load(Ordering::Relaxed))
+    }
+
+    #[test]
+    fn mapping_parses_range_and_curve_from_param_ops() {
+        let mapping = param_mapping("param:3:20:20000:exp").unwrap();
+        assert_eq!(mapping.param, 3);
+        assert_eq!(mapping.curve, MidiCurve::Exponential);
+        assert_eq!(mapping.value(0.0), 20.0);
+        assert!((mapping.value(0.5) - 632.455_532).abs() < 1e-6);
+        assert_eq!(param_mapping("param:1").unwrap().value(0.25), 0.25);
+        assert_eq!(param_mapping("param:16"), None);
+        assert_eq!(param_mapping("param:1:0"), None);
+        assert_eq!(param_mapping("cc:1"), None);
+    }
+
+    #[test]
+    fn armed_learn_binds_the_next_control_change() {
+        let (learned_tx, learned_rx) = crossbeam_channel::unbounded();
+        let learn = MidiLearn::new(Default::default(), Some(learned_tx));
+        learn.control_change(0, 74, 1.0);
+        assert_eq!(param(&learn, 2), 0.0);
+
+        learn.arm(param_mapping("param:2:-1:1"));
+        learn.control_change(1, 74, 1.0);
+        assert_eq!(param(&learn, 2), 1.0);
+        assert_eq!(
+            learned_rx
+                .try_recv()
+                .map(|binding| (binding.channel, binding.controller)),
+            Ok((1, 74))
+        );
+        // Only the learned channel and controller drive the param.
+        learn.control_change(0, 74, 0.0);
+        learn.control_change(1, 71, 0.0);
+        assert_eq!(param(&learn, 2), 1.0);
+        learn.control_change(1, 74, 0.5);
+        assert_eq!(param(&learn, 2), 0.0);
+    }
+}
@@ -0,0 +1,107 @@
+//! MIDI output: the audio callback pushes the events `midiout:`/`ccout:` ops
+//! emit into a ring, and a worker thread sends them to the output port so a
+//! slow or blocking backend never stalls audio.
+
+use crate::midi::{MidiPortSelection, list_ports, select_port};
+use anyhow::{Result, anyhow};
+use audio_ops::{MIDI_EVENT_RING_CAPACITY, MidiEvent, encode_message};
+use midir::{MidiOutput, MidiOutputConnection};
+use rtrb::{Consumer, Producer, RingBuffer};
+use std::{thread::JoinHandle, time::Duration};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Destination of outgoing MIDI events.
+pub trait MidiSink: Send + 'static {
+    fn send(&mut self, message: &[u8]) -> Result<()>;
+}
+
+impl MidiSink for MidiOutputConnection {
+    fn send(&mut self, message: &[u8]) -> Result<()> {
+        Ok(MidiOutputConnection::send(self, message)?)
+    }
+}
+
+pub fn list_outputs() -> Result<Vec<String>> {
+    Ok(list_ports(&MidiOutput::new("sound-garden-list-midi")?))
+}
+
+/// Connect the selected output port and start the worker feeding it. The
+/// worker exits once the returned producer is dropped.
+pub fn open_output(selection: &MidiPortSelection) -> Result<Option<(Producer<MidiEvent>, String)>> {
+    if matches!(selection, MidiPortSelection::None) {
+        return Ok(None);
+    }
+    let output = MidiOutput::new("sound-garden-midi")?;
+    let ports = output.ports();
+    let Some(port) = select_port(&output, &ports, selection, "output")? else {
+        return Ok(None);
+    };
+    let name = output
+        .port_name(&port)
+        .unwrap_or_else(|_| "<unknown>".to_string());
+    let connection = output
+        .connect(&port, "sound-garden-midi-out")
+        .map_err(|err| anyhow!("{err}"))?;
+    let (producer, consumer) = RingBuffer::<MidiEvent>::new(MIDI_EVENT_RING_CAPACITY);
+    spawn_worker(consumer, connection);
+    Ok(Some((producer, name)))
+}
+
+/// Send events until the producer side is dropped. Failed sends are logged
+/// once per run of failures rather than per event.
+pub fn spawn_worker(mut consumer: Consumer<MidiEvent>, mut sink: impl MidiSink) -> JoinHandle<()> {
+    std::thread::spawn(move || {
+        let mut failing = false;
+        loop {
+            let Ok(event) = consumer.pop() else {
+                if consumer.is_abandoned() {
+                    break;
+                }
+                std::thread::sleep(POLL_INTERVAL);
+                continue;
+            };
+            let (message, len) = encode_message(&event);
+            match sink.send(&message[..len]) {
+                Ok(()) => failing = false,
+                Err(err) => {
+                    if !failing {
+                        log::warn!("MIDI output failed: {err}");
+                    }
+                    failing = true;
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct MemorySink(Arc<Mutex<Vec<Vec<u8>>>>);
+
+    impl MidiSink for MemorySink {
+        fn send(&mut self, message: &[u8]) -> Result<()> {
+            self.0.lock().unwrap().push(message.to_vec());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn worker_sends_events_until_the_producer_is_dropped() {
+        let sink = MemorySink::default();
+        let (mut producer, consumer) = RingBuffer::<MidiEvent>::new(8);
+        let worker = spawn_worker(consumer, sink.clone());
+        producer.push(MidiEvent::note_on(0, 60, 1.0)).unwrap();
+        producer.push(MidiEvent::note_off(0, 60)).unwrap();
+        drop(producer);
+        worker.join().unwrap();
+        assert_eq!(
+            *sink.0.lock().unwrap(),
+            [vec![0x90, 60, 127], vec![0x80, 60, 0]]
+        );
+    }
+}
@@ -0,0 +1,164 @@
+//! Sample-accurate placement of incoming MIDI events.
+//!
+//! The MIDI callback maps midir's timestamps onto a clock shared with the
+//! audio thread, and the audio callback maps that clock onto frames of the
+//! buffer it renders. Both relations are smoothed so scheduling jitter on
+//! either thread doesn't reach the events. Events are played one buffer late,
+//! at their offset from the start of the previous callback, which keeps
+//! latency constant instead of snapping every event to a buffer boundary.
+
+use std::time::Instant;
+
+/// Upward drift rate of the MIDI clock offset per event; downward jumps
+/// apply immediately because delivery delays only ever make events late.
+const OFFSET_DRIFT: f64 = 1.0 / 64.0;
+/// Weight of each callback's measured start time against the prediction.
+const CALLBACK_SMOOTHING: f64 = 0.05;
+
+/// Microseconds elapsed on the shared clock.
+pub fn now_micros(epoch: Instant) -> u64 {
+    epoch.elapsed().as_micros() as u64
+}
+
+/// MIDI thread side: maps midir timestamps (microseconds from an arbitrary,
+/// backend-specific origin) to microseconds on the shared clock.
+pub struct MidiClock {
+    epoch: Instant,
+    /// Shared clock minus midir clock, smoothed.
+    offset: Option<f64>,
+}
+
+impl MidiClock {
+    pub fn new(epoch: Instant) -> Self {
+        MidiClock {
+            epoch,
+            offset: None,
+        }
+    }
+
+    pub fn timestamp(&mut self, midir_micros: u64) -> u64 {
+        self.map(midir_micros, now_micros(self.epoch))
+    }
+
+    fn map(&mut self, midir_micros: u64, now: u64) -> u64 {
+        let measured = now as f64 - midir_micros as f64;
+        let offset = match self.offset {
+            Some(offset) if measured >= offset => offset + (measured - offset) * OFFSET_DRIFT,
+            _ => measured,
+        };
+        self.offset = Some(offset);
+        // Never stamp an event 0, which means "unscheduled".
+        (midir_micros as f64 + offset).max(1.0) as u64
+    }
+}
+
+/// Audio thread side: tracks callback start times on the shared clock and
+/// turns event timestamps into frame offsets inside the current buffer.
+pub struct FrameScheduler {
+    epoch: Instant,
+    sample_rate: f64,
+    /// Smoothed start of the previous and current callbacks, microseconds.
+    previous_start: f64,
+    current_start: Option<f64>,
+    current_frames: usize,
+}
+
+impl FrameScheduler {
+    pub fn new(epoch: Instant, sample_rate: u32) -> Self {
+        FrameScheduler {
+            epoch,
+            sample_rate: sample_rate as f64,
+            previous_start: 0.0,
+            current_start: None,
+            current_frames: 0,
+        }
+    }
+
+    pub fn begin_buffer(&mut self, frames: usize) {
+        self.begin(now_micros(self.epoch), frames);
+    }
+
+    fn begin(&mut self, now: u64, frames: usize) {
+        let now = now as f64;
+        let duration = |frames: usize| frames as f64 * 1e6 / self.sample_rate;
+        let start = match self.current_start {
+            Some(start) => {
+                let predicted = start + duration(self.current_frames);
+                // Underruns and device restarts break the relation: resync.
+                if (now - predicted).abs() > duration(frames.max(self.current_frames)) {
+                    self.previous_start = now - duration(frames);
+                    now
+                } else {
+                    self.previous_start = start;
+                    predicted + (now - predicted) * CALLBACK_SMOOTHING
+                }
+            }
+            None => {
+                self.previous_start = now - duration(frames);
+                now
+            }
+        };
+        self.current_start = Some(start);
+        self.current_frames = frames;
+    }
+
+    /// Frame on which an event stamped `timestamp` plays: its offset from the
+    /// previous callback's start, so events that arrived while that buffer
+    /// was playing keep their spacing. Offsets past the current buffer belong
+    /// to a later one and are left for it to schedule.
+    pub fn frame_offset(&self, timestamp: u64) -> usize {
+        if timestamp == 0 || self.current_frames == 0 {
+            return 0;
+        }
+        let offset = (timestamp as f64 - self.previous_start) * self.sample_rate / 1e6;
+        offset.max(0.0) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn midi_clock_follows_the_fastest_delivery() {
+        let mut clock = MidiClock::new(Instant::now());
+        assert_eq!(clock.map(1_000, 5_000), 5_000);
+        // A late delivery barely moves the offset...
+        assert_eq!(clock.map(2_000, 7_000), 6_015);
+        // ...while an earlier one resets it.
+        assert_eq!(clock.map(3_000, 6_500), 6_500);
+    }
+
+    #[test]
+    fn events_keep_their_spacing_one_buffer_late() {
+        let mut scheduler = FrameScheduler::new(Instant::now(), 1000);
+        scheduler.begin(100_000, 10);
+        // The previous buffer is assumed to have started 10 ms earlier.
+        assert_eq!(scheduler.frame_offset(90_000), 0);
+        assert_eq!(scheduler.frame_offset(95_500), 5);
+        assert_eq!(scheduler.frame_offset(99_999), 9);
+        scheduler.begin(110_000, 10);
+        assert_eq!(scheduler.frame_offset(103_000), 3);
+        // Too early clamps to the first frame; unscheduled is 0.
+        assert_eq!(scheduler.frame_offset(50_000), 0);
+        assert_eq!(scheduler.frame_offset(0), 0);
+        // Arrived while this callback runs: deferred past the buffer, then
+        // played on its own frame of the next one.
+        assert_eq!(scheduler.frame_offset(112_000), 12);
+        assert_eq!(scheduler.frame_offset(120_000), 20);
+        scheduler.begin(120_000, 10);
+        assert_eq!(scheduler.frame_offset(112_000), 2);
+    }
+
+    #[test]
+    fn callback_jitter_is_smoothed_and_gaps_resync() {
+        let mut scheduler = FrameScheduler::new(Instant::now(), 1000);
+        scheduler.begin(100_000, 10);
+        scheduler.begin(112_000, 10);
+        // 2 ms late callback moves the estimate by 0.1 ms only.
+        assert_eq!(scheduler.current_start, Some(110_100.0));
+        scheduler.begin(200_000, 10);
+        assert_eq!(scheduler.current_start, Some(200_000.0));
+        assert_eq!(scheduler.previous_start, 190_000.0);
+    }
+}
@@ -1,15 +1,107 @@
 use anyhow::Result;
+use audio_ops::{MIDI_CHANNELS, MIDI_EVENT_RING_CAPACITY, MidiEvent, MidiEventKind, write_smf};
 use audio_vm::{CHANNELS, Sample};
 use chrono::Local;
 use crossbeam_channel::{Receiver, Sender, TryRecvError};
 use hound::{SampleFormat, WavSpec, WavWriter};
-use rtrb::Consumer;
+use rtrb::{Consumer, Producer, RingBuffer};
 
 const POLL_INTERVAL_MS: u64 = 10;
 
+/// Audio thread side of the recorder. MIDI events are stamped with their
+/// position in the sample stream, counting only samples that made it into
+/// the ring, so they stay aligned with the WAV.
+pub struct RecordFeed {
+    samples: Producer<Sample>,
+    midi: Producer<(u64, MidiEvent)>,
+    position: u64,
+}
+
+pub struct RecordTap {
+    samples: Consumer<Sample>,
+    midi: Consumer<(u64, MidiEvent)>,
+}
+
+pub fn feed(capacity: usize) -> (RecordFeed, RecordTap) {
+    let (samples_tx, samples) = RingBuffer::<Sample>::new(capacity);
+    let (midi_tx, midi) = RingBuffer::<(u64, MidiEvent)>::new(MIDI_EVENT_RING_CAPACITY);
+    (
+        RecordFeed {
+            samples: samples_tx,
+            midi: midi_tx,
+            position: 0,
+        },
+        RecordTap { samples, midi },
+    )
+}
+
+impl RecordFeed {
+    pub fn push_sample(&mut self, sample: Sample) {
+        if self.samples.push(sample).is_ok() {
+            self.position += 1;
+        }
+    }
+
+    /// An event played on the frame pushed next.
+    pub fn push_midi(&mut self, event: MidiEvent) {
+        self.midi.push((self.position, event)).ok();
+    }
+}
+
+/// MIDI played during a take, in frames from its start.
+struct MidiTake {
+    path: String,
+    start: u64,
+    events: Vec<(u64, MidiEvent)>,
+}
+
+impl MidiTake {
+    fn new(path: String, start: u64) -> Self {
+        MidiTake {
+            path,
+            start,
+            events: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, position: u64, event: MidiEvent) {
+        if let Some(offset) = position.checked_sub(self.start) {
+            self.events.push((offset / CHANNELS as u64, event));
+        }
+    }
+
+    /// The take as a MIDI file ending at `position`, `None` when nothing was
+    /// played. Notes still held at the end are released there.
+    fn into_smf(mut self, position: u64, sample_rate: u32) -> Option<Vec<u8>> {
+        let frames = position.saturating_sub(self.start) / CHANNELS as u64;
+        // Events of samples dropped with the end of the take.
+        self.events.retain(|&(frame, _)| frame < frames);
+        if self.events.is_empty() {
+            return None;
+        }
+        // Sounding notes are known only once the dropped note-offs are gone.
+        let mut sounding = [0u128; MIDI_CHANNELS];
+        for (_, event) in &self.events {
+            let bit = 1u128 << event.note;
+            match event.kind {
+                MidiEventKind::NoteOn => sounding[event.channel as usize] |= bit,
+                MidiEventKind::NoteOff => sounding[event.channel as usize] &= !bit,
+                _ => {}
+            }
+        }
+        for (channel, notes) in sounding.into_iter().enumerate() {
+            for note in (0..128).filter(|note| notes & 1 << note != 0) {
+                self.events
+                    .push((frames, MidiEvent::note_off(channel as u8, note)));
+            }
+        }
+        Some(write_smf(&self.events, frames, sample_rate))
+    }
+}
+
 pub fn main(
     sample_rate: u32,
-    mut consumer: Consumer<Sample>,
+    mut tap: RecordTap,
     rx: Receiver<bool>,
     _tx: Sender<()>,
 ) -> Result<()> {
@@ -20,14 +112,35 @@ pub fn main(
         sample_format: SampleFormat::Int,
     };
     let mut writer: Option<WavWriter<std::io::BufWriter<std::fs::File>>> = None;
+    let mut midi_take: Option<MidiTake> = None;
+    // Samples popped so far, the clock MIDI events are stamped with.
+    let mut position = 0;
     loop {
         match rx.try_recv() {
             Ok(on) => {
                 writer.take().and_then(|w| w.finalize().ok());
-                while consumer.pop().is_ok() {}
+                // Every event played with the samples written is in the ring
+                // by now.
+                while let Ok((stamp, event)) = tap.midi.pop() {
+                    if let Some(take) = midi_take.as_mut() {
+                        take.push(stamp, event);
+                    }
+                }
+                if let Some(take) = midi_take.take() {
+                    let path = take.path.clone();
+                    if let Some(smf) = take.into_smf(position, sample_rate)
+                        && let Err(err) = std::fs::write(&path, smf)
+                    {
+                        log::warn!("Failed to write {path}: {err}");
+                    }
+                }
+                while tap.samples.pop().is_ok() {
+                    position += 1;
+                }
                 if on {
-                    let filename = format!("{}.wav", Local::now().to_rfc3339());
-                    writer = Some(WavWriter::create(filename, spec)?);
+                    let name = Local::now().to_rfc3339();
+                    writer = Some(WavWriter::create(format!("{name}.wav"), spec)?);
+                    midi_take = Some(MidiTake::new(format!("{name}.mid"), position));
                 }
             }
             Err(TryRecvError::Disconnected) => {
@@ -40,9 +153,49 @@ pub fn main(
             writer.as_mut().map(|w| w.write_sample(sample));
             true
         };
-        while let Ok(sample) = consumer.pop() {
+        while let Ok(sample) = tap.samples.pop() {
             write(sample);
+            position += 1;
+        }
+        while let Ok((stamp, event)) = tap.midi.pop() {
+            if let Some(take) = midi_take.as_mut() {
+                take.push(stamp, event);
+            }
         }
         std::thread::sleep(std::time::Duration::from_millis(POLL_INTERVAL_MS));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use audio_ops::parse_smf;
+
+    #[test]
+    fn take_keeps_events_played_with_its_samples() {
+        let (mut feed, mut tap) = feed(64);
+        feed.push_midi(MidiEvent::note_on(0, 59, 1.0));
+        for _ in 0..4 * CHANNELS {
+            feed.push_sample(0.0);
+        }
+        let mut take = MidiTake::new(String::new(), 2 * CHANNELS as u64);
+        feed.push_midi(MidiEvent::note_on(0, 60, 1.0));
+        feed.push_sample(0.0);
+        feed.push_sample(0.0);
+        feed.push_midi(MidiEvent::note_off(0, 60));
+        while let Ok((stamp, event)) = tap.midi.pop() {
+            take.push(stamp, event);
+        }
+
+        // Recording stopped after the frame of the note on, so the held note
+        // is released at the end of the take instead.
+        let smf = take.into_smf(5 * CHANNELS as u64, 48_000).unwrap();
+        let events = parse_smf(&smf).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].event.kind, MidiEventKind::NoteOn);
+        assert_eq!((events[0].time * 48_000.0).round(), 2.0);
+        assert_eq!(events[1].event.kind, MidiEventKind::NoteOff);
+        assert_eq!((events[1].event.channel, events[1].event.note), (0, 60));
+        assert_eq!((events[1].time * 48_000.0).round(), 3.0);
+    }
+}
@@ -8,6 +8,6 @@ pub use self::{
     denormal::enable_flush_to_zero,
     op::Op,
     sample::{AtomicFrame, AtomicSample, CHANNELS, Frame, Sample},
-    stack::Stack,
+    stack::{STACK_SIZE, Stack},
     vm::{Program, Statement, VM, migrate_program_state},
 };
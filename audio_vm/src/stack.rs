@@ -1,6 +1,6 @@
 use crate::sample::{CHANNELS, Frame, Sample};
 
-const STACK_SIZE: usize = 16;
+pub const STACK_SIZE: usize = 16;
 const STACK_CAPACITY: usize = CHANNELS * STACK_SIZE;
 
 /// Simple fixed capacity stack tolerant to {over,under}flows.
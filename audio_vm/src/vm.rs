@@ -132,11 +132,16 @@ impl VM {
         let mut garbage = std::mem::replace(&mut self.active_program, program);
         migrate_program_state(&mut self.active_program, &mut garbage);
         // Arm the declicker only when the VM is audible; a silent VM cannot click.
-        self.declick_pending = self.declick_duration > 0
-            && (matches!(self.status, Status::Play) || self.pause_countdown > 0);
+        self.declick_pending = self.declick_duration > 0 && self.is_running();
         garbage
     }
 
+    /// Whether the next frame performs the active program, i.e. the VM is
+    /// playing or still fading out after a pause.
+    pub fn is_running(&self) -> bool {
+        matches!(self.status, Status::Play) || self.pause_countdown > 0
+    }
+
     #[cfg_attr(feature = "allocation-checks", no_alloc)]
     pub fn next_frame(&mut self) -> Frame {
         let frame = match self.status {
@@ -424,9 +429,28 @@ mod tests {
         vm.pause();
         assert_eq!(vm.next_frame(), [10.0, 20.0]);
         assert_eq!(vm.next_frame(), [5.0, 10.0]);
+        assert!(!vm.is_running());
         assert_eq!(vm.next_frame(), [0.0, 0.0]);
     }
 
+    #[test]
+    fn vm_runs_while_playing_and_fading_out() {
+        let mut vm = VM::new();
+        vm.set_xfade_duration(1.0);
+        assert!(!vm.is_running());
+
+        vm.play();
+        assert!(vm.is_running());
+        vm.pause();
+        assert!(vm.is_running());
+        vm.next_frame();
+        assert!(!vm.is_running());
+
+        vm.play();
+        vm.stop();
+        assert!(!vm.is_running());
+    }
+
     #[test]
     fn monitor_tracks_selected_statement_or_final_output() {
         let mut vm = VM::new();
@@ -1,6 +1,6 @@
-use audio_ops::pure::clip;
+use audio_ops::{Transport, pure::clip};
 use audio_program::{Context, TextOp, compile_program};
-use audio_vm::{CHANNELS, Program, VM};
+use audio_vm::{CHANNELS, Program, Sample, VM};
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use std::io::Read;
 
@@ -43,8 +43,10 @@ where
 {
     let channels = config.channels as usize;
 
+    let sample_period = (config.sample_rate as Sample).recip();
+    let mut ctx = Context::new();
     let mut vm = VM::new();
-    vm.load_program(parse_program(text, config.sample_rate));
+    vm.load_program(parse_program(text, config.sample_rate, &mut ctx));
     vm.play();
 
     let err_fn = |err| eprintln!("an error occurred on stream: {}", err);
@@ -53,7 +55,7 @@ where
         config,
         move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
             audio_vm::enable_flush_to_zero();
-            write_data(data, channels, &mut vm)
+            write_data(data, channels, &mut vm, &ctx.transport, sample_period)
         },
         err_fn,
         None,
@@ -65,18 +67,24 @@ where
     Ok(())
 }
 
-fn write_data<T>(output: &mut [T], channels: usize, vm: &mut VM)
-where
+fn write_data<T>(
+    output: &mut [T],
+    channels: usize,
+    vm: &mut VM,
+    transport: &Transport,
+    sample_period: Sample,
+) where
     T: cpal::Sample + cpal::SizedSample + cpal::FromSample<f32>,
 {
     for frame in output.chunks_mut(channels) {
         for (sample, &value) in frame.iter_mut().zip(vm.next_frame().iter()) {
             *sample = T::from_sample(clip(value) as f32);
         }
+        transport.advance(sample_period);
     }
 }
 
-fn parse_program(s: &str, sample_rate: u32) -> Program {
+fn parse_program(s: &str, sample_rate: u32, ctx: &mut Context) -> Program {
     let ops = s
         .split_whitespace()
         .map(|op| TextOp {
@@ -84,5 +92,5 @@ fn parse_program(s: &str, sample_rate: u32) -> Program {
             op: op.to_string(),
         })
         .collect::<Vec<_>>();
-    compile_program(&ops, sample_rate, &mut Context::new())
+    compile_program(&ops, sample_rate, ctx)
 }
@@ -1,4 +1,4 @@
-use audio_ops::pure::clip;
+use audio_ops::{MAX_MIDI_EVENTS_PER_FRAME, SmfEvent, parse_smf, pure::clip};
 use audio_program::{Context, TextOp, compile_program};
 use audio_vm::{CHANNELS, Program, Sample, VM};
 use hound::{SampleFormat, WavSpec, WavWriter};
@@ -12,17 +12,16 @@ fn main() {
         .expect("Failed to read stdin");
 
     let mut stats_enabled = false;
-    let args = std::env::args()
-        .skip(1)
-        .filter(|arg| {
-            if arg == "--stats" {
-                stats_enabled = true;
-                false
-            } else {
-                true
-            }
-        })
-        .collect::<Vec<_>>();
+    let mut midi_path = None;
+    let mut args = Vec::new();
+    let mut all_args = std::env::args().skip(1);
+    while let Some(arg) = all_args.next() {
+        match arg.as_str() {
+            "--stats" => stats_enabled = true,
+            "--midi" => midi_path = Some(all_args.next().expect("Please provide MIDI file path.")),
+            _ => args.push(arg),
+        }
+    }
     let mut args = args.into_iter();
 
     let duration = args
@@ -33,6 +32,15 @@ fn main() {
 
     let sample_rate: u32 = 48000;
 
+    let midi = midi_path
+        .map(|path| {
+            let bytes = std::fs::read(&path).expect("Failed to read MIDI file.");
+            parse_smf(&bytes).unwrap_or_else(|err| panic!("Failed to parse {path}: {err}"))
+        })
+        .unwrap_or_default();
+    let mut midi = midi.into_iter().peekable();
+    let mut midi_events = Vec::with_capacity(MAX_MIDI_EVENTS_PER_FRAME);
+
     let spec = WavSpec {
         channels: CHANNELS as _,
         sample_rate,
@@ -42,16 +50,28 @@ fn main() {
 
     let mut writer = WavWriter::create(output, spec).expect("Failed to create a file.");
 
+    let mut ctx = Context::new();
     let mut vm = VM::new();
-    vm.load_program(parse_program(&text, sample_rate));
+    vm.load_program(parse_program(&text, sample_rate, &mut ctx));
     vm.play();
 
     audio_vm::enable_flush_to_zero();
 
     let mut stats = Stats::new();
+    let sample_period = (sample_rate as Sample).recip();
     let t = Instant::now();
-    for _ in 0..((duration * (sample_rate as f64)) as _) {
+    for index in 0..((duration * (sample_rate as f64)) as u64) {
+        // Events land on the nearest frame; overflow waits for the next one.
+        midi_events.clear();
+        while midi_events.len() < MAX_MIDI_EVENTS_PER_FRAME
+            && let Some(SmfEvent { event, .. }) =
+                midi.next_if(|event| (event.time * sample_rate as f64).round() as u64 <= index)
+        {
+            midi_events.push(event);
+        }
+        ctx.midi.set_events(&midi_events);
         let frame = vm.next_frame();
+        ctx.transport.advance(sample_period);
         if stats_enabled {
             stats.observe(&frame);
         }
@@ -128,7 +148,7 @@ fn dbfs(value: Sample) -> String {
     }
 }
 
-fn parse_program(s: &str, sample_rate: u32) -> Program {
+fn parse_program(s: &str, sample_rate: u32, ctx: &mut Context) -> Program {
     let ops = s
         .split_whitespace()
         .map(|op| TextOp {
@@ -136,5 +156,5 @@ fn parse_program(s: &str, sample_rate: u32) -> Program {
             op: op.to_string(),
         })
         .collect::<Vec<_>>();
-    compile_program(&ops, sample_rate, &mut Context::new())
+    compile_program(&ops, sample_rate, ctx)
 }
@@ -55,7 +55,8 @@ fn main() -> Result<()> {
             Arg::new("midi")
                 .long("midi")
                 .value_name("DEVICE")
-                .help("Connect a MIDI input for the embedded audio server: 'auto', device index, or case-insensitive name substring."),
+                .action(clap::ArgAction::Append)
+                .help("Connect a MIDI input for the embedded audio server: 'auto', device index, or case-insensitive name substring. Repeat to merge several inputs."),
         )
         .arg(
             Arg::new("list-midi")
@@ -63,6 +64,18 @@ fn main() -> Result<()> {
                 .action(clap::ArgAction::SetTrue)
                 .help("List available MIDI input devices and exit."),
         )
+        .arg(
+            Arg::new("midi-out")
+                .long("midi-out")
+                .value_name("DEVICE")
+                .help("Connect a MIDI output for the embedded audio server: 'auto', device index, or case-insensitive name substring."),
+        )
+        .arg(
+            Arg::new("list-midi-out")
+                .long("list-midi-out")
+                .action(clap::ArgAction::SetTrue)
+                .help("List available MIDI output devices and exit."),
+        )
         .get_matches();
 
     if matches.get_flag("list-midi") {
@@ -71,6 +84,12 @@ fn main() -> Result<()> {
         }
         return Ok(());
     }
+    if matches.get_flag("list-midi-out") {
+        for line in audio_server::list_midi_outputs()? {
+            println!("{line}");
+        }
+        return Ok(());
+    }
 
     let filename = matches
         .get_one::<String>("FILENAME")
@@ -79,16 +98,33 @@ fn main() -> Result<()> {
 
     let node_repo = Arc::new(Mutex::new(NodeRepository::load(&filename)));
 
-    let midi = matches
-        .get_one::<String>("midi")
-        .map(|selection| {
-            if selection == "auto" {
-                audio_server::MidiInputSelection::Auto
-            } else {
-                audio_server::MidiInputSelection::Match(selection.clone())
-            }
-        })
-        .unwrap_or_default();
+    let port_selection = |selection: &String| {
+        if selection == "auto" {
+            audio_server::MidiPortSelection::Auto
+        } else {
+            audio_server::MidiPortSelection::Match(selection.clone())
+        }
+    };
+    let (midi_status_tx, midi_status_rx) = crossbeam_channel::unbounded();
+    let (midi_learned_tx, midi_learned_rx) = crossbeam_channel::unbounded();
+    let options = audio_server::Options {
+        midi: matches
+            .get_many::<String>("midi")
+            .into_iter()
+            .flatten()
+            .map(port_selection)
+            .collect(),
+        midi_out: matches
+            .get_one::<String>("midi-out")
+            .map(port_selection)
+            .unwrap_or_default(),
+        midi_status: Some(midi_status_tx),
+        midi_learned: Some(midi_learned_tx),
+    };
+    // MIDI belongs to an external server, which reports no status.
+    let embedded = !matches.contains_id("audio-port");
+    let midi_status_rx = (embedded && !options.midi.is_empty()).then_some(midi_status_rx);
+    let midi_learned_rx = embedded.then_some(midi_learned_rx);
 
     let audio_control = if let Some(port) = matches.get_one::<String>("audio-port") {
         let address = format!("127.0.0.1:{}", port);
@@ -107,7 +143,7 @@ fn main() -> Result<()> {
         )
     } else {
         Worker::spawn("Audio", 1, move |rx, tx| {
-            audio_server::run_with_options(rx, tx, audio_server::Options { midi });
+            audio_server::run_with_options(rx, tx, options);
         })
     };
 
@@ -116,6 +152,8 @@ fn main() -> Result<()> {
         node_repo,
         audio_control.sender().clone(),
         audio_control.receiver().clone(),
+        midi_status_rx,
+        midi_learned_rx,
     );
 
     let options = eframe::NativeOptions {
@@ -169,6 +207,12 @@ struct SoundGardenApp {
     filename: String,
     audio_tx: Sender<audio_server::Message>,
     monitor_rx: Receiver<audio_server::Monitor>,
+    /// Present when the embedded server has MIDI inputs to report on.
+    midi_status_rx: Option<Receiver<Vec<audio_server::MidiInputStatus>>>,
+    midi_status: Vec<audio_server::MidiInputStatus>,
+    midi_learned_rx: Option<Receiver<MidiBinding>>,
+    /// Param waiting for a knob to be turned.
+    midi_learning: Option<usize>,
     undo_group: u64,
     last_committed_program: Vec<(Id, String)>,
     state: UiState,
@@ -208,12 +252,18 @@ impl SoundGardenApp {
         node_repo: Arc<Mutex<NodeRepository>>,
         audio_tx: Sender<audio_server::Message>,
         monitor_rx: Receiver<audio_server::Monitor>,
+        midi_status_rx: Option<Receiver<Vec<audio_server::MidiInputStatus>>>,
+        midi_learned_rx: Option<Receiver<MidiBinding>>,
     ) -> Self {
         let mut app = Self {
             node_repo,
             filename,
             audio_tx,
             monitor_rx,
+            midi_status_rx,
+            midi_status: Vec::new(),
+            midi_learned_rx,
+            midi_learning: None,
             undo_group: 0,
             last_committed_program: Vec::new(),
             state: UiState::default(),
@@ -227,6 +277,10 @@ impl SoundGardenApp {
         };
         app.sync_from_repo();
         app.update_audio_monitor();
+        let bindings = app.node_repo.lock().unwrap().midi_bindings().to_vec();
+        app.audio_tx
+            .send(audio_server::Message::MidiBindings(bindings))
+            .ok();
         app
     }
 
@@ -326,6 +380,31 @@ impl SoundGardenApp {
         render_nodes_text(self.state.nodes.iter())
     }
 
+    /// Arm MIDI learn for the `param:N` node at the cursor, or cancel it.
+    fn toggle_midi_learn(&mut self) {
+        let mapping = if self.midi_learning.is_some() {
+            None
+        } else {
+            if self.midi_learned_rx.is_none() {
+                // An external server has no way to report the binding back.
+                log::warn!("MIDI learn needs the embedded audio server.");
+                return;
+            }
+            let mapping = self
+                .node_at_cursor()
+                .and_then(|(node, _)| audio_server::param_mapping(&node.text));
+            if mapping.is_none() {
+                log::warn!("MIDI learn needs a param:N node at the cursor.");
+                return;
+            }
+            mapping
+        };
+        self.midi_learning = mapping.map(|mapping| mapping.param);
+        self.audio_tx
+            .send(audio_server::Message::MidiLearn(mapping))
+            .ok();
+    }
+
     fn reset_oscilloscope(&mut self) {
         self.oscilloscope_values.clear();
         self.oscilloscope_min = -1.0;
@@ -402,6 +481,11 @@ impl SoundGardenApp {
                     .send(audio_server::Message::Play(self.state.play))
                     .ok();
             }
+            Action::ResetTransport => {
+                self.audio_tx
+                    .send(audio_server::Message::ResetTransport)
+                    .ok();
+            }
             Action::ToggleRecord => {
                 self.state.record = !self.state.record;
                 self.audio_tx
@@ -464,6 +548,7 @@ impl SoundGardenApp {
                 self.state.show_pattern_highlights = !self.state.show_pattern_highlights;
                 self.update_audio_monitor();
             }
+            Action::MidiLearn => self.toggle_midi_learn(),
             Action::OscilloscopeZoomIn => self.state.oscilloscope_zoom += 1,
             Action::OscilloscopeZoomOut => self.state.oscilloscope_zoom -= 1,
             Action::MoveRightToLeft => self.move_nodes_on_cursor_line(-1.0, |node, cursor| {
@@ -1173,6 +1258,29 @@ impl SoundGardenApp {
                 FOREGROUND_COLOR,
             );
         }
+
+        let midi = if let Some(param) = self.midi_learning {
+            Some(format!("midi learn: turn a knob for param:{param}"))
+        } else if !self.midi_status.is_empty() {
+            let status = self
+                .midi_status
+                .iter()
+                .map(|input| input.to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            Some(format!("midi: {status}"))
+        } else {
+            None
+        };
+        if let Some(midi) = midi {
+            painter.text(
+                Pos2::new(rect.max.x - 11.0, rect.min.y + 5.0),
+                Align2::RIGHT_TOP,
+                midi,
+                FontId::monospace(MODELINE_FONT_SIZE),
+                FOREGROUND_COLOR,
+            );
+        }
     }
 
     fn draw_op_list(&mut self, ctx: &egui::Context) {
@@ -1314,6 +1422,28 @@ impl eframe::App for SoundGardenApp {
         if received_monitor_frame {
             ctx.request_repaint();
         }
+        let learned = self
+            .midi_learned_rx
+            .as_ref()
+            .map(|rx| rx.try_iter().collect::<Vec<_>>())
+            .unwrap_or_default();
+        if !learned.is_empty() {
+            self.midi_learning = None;
+            for binding in &learned {
+                self.node_repo.lock().unwrap().set_midi_binding(*binding);
+            }
+            SoundGardenApp::save(self);
+        }
+        if self.midi_learning.is_some() {
+            ctx.request_repaint_after(std::time::Duration::from_millis(100));
+        }
+        if let Some(midi_status_rx) = &self.midi_status_rx {
+            while let Ok(status) = midi_status_rx.try_recv() {
+                self.midi_status = status;
+            }
+            // Devices come and go without any UI event to wake us.
+            ctx.request_repaint_after(std::time::Duration::from_secs(1));
+        }
 
         for action in self.collect_input(&ctx) {
             match action {
@@ -1364,6 +1494,7 @@ enum Action {
     CutNode,
     CommitProgram,
     PlayPause,
+    ResetTransport,
     ToggleRecord,
     Undo,
     Redo,
@@ -1389,6 +1520,7 @@ enum Action {
     SplitLine,
     CopyCurrentLine,
     CopyProgram,
+    MidiLearn,
 }
 
 fn key_action(key: egui::Key, modifiers: egui::Modifiers, mode: Mode) -> Option<Action> {
@@ -1437,6 +1569,7 @@ fn key_action(key: egui::Key, modifiers: egui::Modifiers, mode: Mode) -> Option<
             egui::Key::D if !shift => Some(Action::DeleteNode),
             egui::Key::D if shift => Some(Action::DeleteLine),
             egui::Key::Enter => Some(Action::CommitProgram),
+            egui::Key::Backslash if shift => Some(Action::ResetTransport),
             egui::Key::Backslash => Some(Action::PlayPause),
             egui::Key::R if !shift => Some(Action::ToggleRecord),
             egui::Key::U if !shift => Some(Action::Undo),
@@ -1459,6 +1592,7 @@ fn key_action(key: egui::Key, modifiers: egui::Modifiers, mode: Mode) -> Option<
             egui::Key::V if !shift => Some(Action::ToggleOscilloscope),
             egui::Key::V if shift => Some(Action::ResetOscilloscope),
             egui::Key::P if !shift => Some(Action::TogglePatternHighlights),
+            egui::Key::M if !shift => Some(Action::MidiLearn),
             _ => None,
         },
         Mode::Insert => match key {
@@ -1579,19 +1713,21 @@ fn remap(x: f64, from_min: f64, from_max: f64, to_min: f64, to_max: f64) -> f64
 fn pattern_text(text: &str) -> Option<(bool, bool, &str)> {
     let (op, pattern) = text.split_once(':')?;
     match op {
-        "pat" => Some((false, false, pattern)),
-        "gate" | "trig" => Some((false, true, pattern)),
+        "pat" | "chord" => Some((false, false, pattern)),
+        "gate" | "trig" | "seq" => Some((false, true, pattern)),
         "cpat" => Some((true, false, pattern)),
-        "cgate" | "ctrig" => Some((true, true, pattern)),
+        "cgate" | "ctrig" | "cseq" => Some((true, true, pattern)),
         _ => None,
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq)]
 enum VisualPatternElement {
     Atom((usize, usize)),
     Group(Vec<VisualPatternElement>),
     Alternate(Vec<Vec<VisualPatternElement>>),
+    Polymetric(Vec<VisualPatternElement>, usize),
+    Nudge(Box<VisualPatternElement>, f64),
 }
 
 fn active_pattern_span(
@@ -1600,32 +1736,147 @@ fn active_pattern_span(
     phase: f64,
     cycle: usize,
 ) -> Option<(usize, usize)> {
-    active_span(
-        &mut VisualPatternParser::new(pattern, dense).parse(),
-        phase,
-        cycle,
+    let (pattern, swing) = split_swing(pattern);
+    let elements = VisualPatternParser::new(pattern, dense).parse();
+    let cells = visual_cells(&elements, cycle, swing);
+    let phase = phase.rem_euclid(1.0);
+    if cells.first().is_some_and(|&(start, _, _)| phase < start) {
+        // Before a delayed first cell the previous cycle's last cell rings on,
+        // wrapping to the end of the period on cycle 0 like the engine.
+        let previous = cycle
+            .checked_sub(1)
+            .unwrap_or_else(|| visual_period(&elements) - 1);
+        return visual_cells(&elements, previous, swing)
+            .last()
+            .map(|&(_, _, span)| span);
+    }
+    cells
+        .iter()
+        .find(|&&(start, end, _)| phase >= start && phase < end)
+        .or(cells.last())
+        .map(|&(_, _, span)| span)
+}
+
+/// Cycles until the pattern repeats, the engine's `pattern_period`.
+fn visual_period(elements: &[VisualPatternElement]) -> usize {
+    fn gcd(a: usize, b: usize) -> usize {
+        if b == 0 { a } else { gcd(b, a % b) }
+    }
+    fn lcm(a: usize, b: usize) -> usize {
+        a / gcd(a, b).max(1) * b
+    }
+    elements.iter().fold(1, |period, element| {
+        let element_period = match element {
+            VisualPatternElement::Atom(_) => 1,
+            VisualPatternElement::Group(children) => visual_period(children),
+            VisualPatternElement::Alternate(alternatives) => alternatives
+                .iter()
+                .fold(alternatives.len(), |period, alternative| {
+                    lcm(period, visual_period(alternative))
+                }),
+            VisualPatternElement::Polymetric(children, steps) => lcm(
+                children.len() / gcd(children.len(), *steps).max(1),
+                visual_period(children),
+            ),
+            VisualPatternElement::Nudge(element, _) => {
+                visual_period(std::slice::from_ref(element.as_ref()))
+            }
+        };
+        lcm(period, element_period.max(1))
+    })
+}
+
+/// Strip a `:swing:R` suffix; an unparsable ratio highlights as straight time.
+fn split_swing(pattern: &str) -> (&str, f64) {
+    let Some((pattern, swing)) = pattern.rsplit_once(":swing:") else {
+        return (pattern, 0.0);
+    };
+    let swing = match swing.split_once('/') {
+        Some((numerator, denominator)) => numerator
+            .parse::<f64>()
+            .ok()
+            .zip(denominator.parse::<f64>().ok())
+            .map(|(numerator, denominator)| numerator / denominator),
+        None => swing.parse::<f64>().ok(),
+    };
+    (
+        pattern,
+        swing
+            .filter(|swing| (0.0..1.0).contains(swing))
+            .unwrap_or(0.0),
     )
 }
 
-fn active_span(
-    elements: &mut [VisualPatternElement],
-    phase: f64,
+/// Flatten visual elements into `(start, end, span)` cells for one cycle,
+/// mirroring the engine's cell flattening including swing and nudges.
+fn visual_cells(
+    elements: &[VisualPatternElement],
     cycle: usize,
-) -> Option<(usize, usize)> {
-    let len = elements.len();
-    if len == 0 {
-        return None;
-    }
-    let position = phase.rem_euclid(1.0) * len as f64;
-    let index = (position as usize).min(len - 1);
-    let local_phase = position - index as f64;
-    match &mut elements[index] {
-        VisualPatternElement::Atom(span) => Some(*span),
-        VisualPatternElement::Group(children) => active_span(children, local_phase, cycle),
-        VisualPatternElement::Alternate(alternatives) => {
-            let alternative_index = cycle % alternatives.len();
-            let alternative = alternatives.get_mut(alternative_index)?;
-            active_span(alternative, local_phase, cycle)
+    swing: f64,
+) -> Vec<(f64, f64, (usize, usize))> {
+    let mut cells = Vec::new();
+    flatten_visual(elements, cycle, 0.0, 1.0, swing, &mut cells);
+    cells
+}
+
+fn flatten_visual(
+    elements: &[VisualPatternElement],
+    cycle: usize,
+    start: f64,
+    duration: f64,
+    swing: f64,
+    cells: &mut Vec<(f64, f64, (usize, usize))>,
+) {
+    let step = duration / elements.len() as f64;
+    for (index, element) in elements.iter().enumerate() {
+        let (element, delay) = match element {
+            VisualPatternElement::Nudge(element, nudge) => (element.as_ref(), *nudge),
+            element if index % 2 == 1 => (element, swing),
+            element => (element, 0.0),
+        };
+        let cell_start = start + step * (index as f64 + delay);
+        let cell_end = start + step * (index + 1) as f64;
+        if delay > 0.0
+            && let Some(previous) = cells.last_mut()
+        {
+            previous.1 = cell_start;
+        }
+        let cell_duration = cell_end - cell_start;
+        match element {
+            VisualPatternElement::Atom(span) => cells.push((cell_start, cell_end, *span)),
+            VisualPatternElement::Group(children) => {
+                flatten_visual(children, cycle, cell_start, cell_duration, 0.0, cells)
+            }
+            VisualPatternElement::Alternate(alternatives) => flatten_visual(
+                &alternatives[cycle % alternatives.len()],
+                cycle,
+                cell_start,
+                cell_duration,
+                0.0,
+                cells,
+            ),
+            VisualPatternElement::Polymetric(children, steps) => {
+                let sub_step = cell_duration / *steps as f64;
+                for step in 0..*steps {
+                    let child = &children[(cycle * steps + step) % children.len()];
+                    flatten_visual(
+                        std::slice::from_ref(child),
+                        cycle,
+                        cell_start + sub_step * step as f64,
+                        sub_step,
+                        0.0,
+                        cells,
+                    );
+                }
+            }
+            VisualPatternElement::Nudge(..) => flatten_visual(
+                std::slice::from_ref(element),
+                cycle,
+                cell_start,
+                cell_duration,
+                0.0,
+                cells,
+            ),
         }
     }
 }
@@ -1679,9 +1930,19 @@ impl<'a> VisualPatternParser<'a> {
         let elements = match self.peek()? {
             '[' => vec![self.group('[', ']')?],
             '<' => vec![self.alternate()?],
+            '{' => vec![self.polymetric()?],
             _ => self.atom()?,
         };
+        let nudge = self.nudge();
+        self.degrade();
         let repeat = self.repeat();
+        let elements = match nudge {
+            Some(nudge) => elements
+                .into_iter()
+                .map(|element| VisualPatternElement::Nudge(Box::new(element), nudge))
+                .collect(),
+            None => elements,
+        };
         Some((0..repeat).flat_map(|_| elements.clone()).collect())
     }
 
@@ -1711,11 +1972,32 @@ impl<'a> VisualPatternParser<'a> {
         (!alternatives.is_empty()).then_some(VisualPatternElement::Alternate(alternatives))
     }
 
+    fn polymetric(&mut self) -> Option<VisualPatternElement> {
+        debug_assert_eq!(self.peek(), Some('{'));
+        self.bump();
+        let children = self.sequence(&['}']);
+        if self.peek() == Some('}') {
+            self.bump();
+        }
+        if self.peek() != Some('%') {
+            return None;
+        }
+        self.bump();
+        let start = self.index;
+        while self.peek().is_some_and(|ch| ch.is_ascii_digit()) {
+            self.bump();
+        }
+        let steps = self.pattern[start..self.index].parse::<usize>().ok()?;
+        (steps > 0 && !children.is_empty())
+            .then_some(VisualPatternElement::Polymetric(children, steps))
+    }
+
     fn atom(&mut self) -> Option<Vec<VisualPatternElement>> {
         let start = self.index;
         let steps = if self.dense && matches!(self.peek()?, 'x' | 'X' | 'e' | '.') {
             let ch = self.bump()?;
             if matches!(ch, 'x' | 'X' | 'e') {
+                self.step_record();
                 self.euclidean_suffix_steps()
             } else {
                 None
@@ -1729,7 +2011,7 @@ impl<'a> VisualPatternParser<'a> {
                     || ch == '*'
                     || ch == '|'
                     || ch.is_whitespace()
-                    || matches!(ch, '[' | '<' | '(')
+                    || matches!(ch, '[' | '<' | '(' | '{' | '}' | '%' | '?')
                 {
                     break;
                 }
@@ -1767,6 +2049,55 @@ impl<'a> VisualPatternParser<'a> {
         None
     }
 
+    /// Skip `seq:` step record suffixes such as `:0.8` and `:r3`.
+    fn step_record(&mut self) {
+        while self.peek() == Some(':') {
+            self.bump();
+            if self.peek() == Some('r') {
+                self.bump();
+            }
+            self.decimal();
+        }
+    }
+
+    fn decimal(&mut self) {
+        while self.peek().is_some_and(|ch| ch.is_ascii_digit()) {
+            self.bump();
+        }
+        let rest = &self.pattern[self.index..];
+        if rest.starts_with('.') && rest[1..].starts_with(|ch: char| ch.is_ascii_digit()) {
+            self.bump();
+            while self.peek().is_some_and(|ch| ch.is_ascii_digit()) {
+                self.bump();
+            }
+        }
+    }
+
+    fn nudge(&mut self) -> Option<f64> {
+        if self.peek() != Some('>') {
+            return None;
+        }
+        let start = self.index + '>'.len_utf8();
+        let rest = &self.pattern[start..];
+        if !rest.starts_with(|ch: char| ch.is_ascii_digit()) {
+            return None;
+        }
+        self.bump();
+        self.decimal();
+        self.pattern[start..self.index]
+            .parse::<f64>()
+            .ok()
+            .filter(|nudge| (0.0..1.0).contains(nudge))
+    }
+
+    fn degrade(&mut self) {
+        if self.peek() != Some('?') {
+            return;
+        }
+        self.bump();
+        self.decimal();
+    }
+
     fn repeat(&mut self) -> usize {
         if self.peek() != Some('*') {
             return 1;
@@ -1816,7 +2147,14 @@ mod tests {
         let (audio_tx, _audio_rx) = crossbeam_channel::unbounded();
         let (_monitor_tx, monitor_rx) = crossbeam_channel::unbounded();
 
-        SoundGardenApp::new(filename, Arc::new(Mutex::new(repo)), audio_tx, monitor_rx)
+        SoundGardenApp::new(
+            filename,
+            Arc::new(Mutex::new(repo)),
+            audio_tx,
+            monitor_rx,
+            None,
+            None,
+        )
     }
 
     fn position(app: &SoundGardenApp, id: u64) -> Point {
@@ -1845,6 +2183,40 @@ mod tests {
         assert_eq!(app.state.cursor.position, Point::new(5.0, 4.0));
     }
 
+    #[test]
+    fn midi_learn_arms_on_param_nodes_and_toggles_off() {
+        let mut app = app_with_nodes(
+            vec![
+                node(1, 0.0, 0.0, "param:2:20:200:exp"),
+                node(2, 0.0, 1.0, "sine"),
+            ],
+            Point::new(0.0, 1.0),
+        );
+
+        let (_midi_learned_tx, midi_learned_rx) = crossbeam_channel::unbounded();
+        app.midi_learned_rx = Some(midi_learned_rx);
+
+        app.handle_action(Action::MidiLearn);
+        assert_eq!(app.midi_learning, None);
+
+        app.handle_action(Action::SetCursor(Point::new(1.0, 0.0)));
+        app.handle_action(Action::MidiLearn);
+        assert_eq!(app.midi_learning, Some(2));
+        app.handle_action(Action::MidiLearn);
+        assert_eq!(app.midi_learning, None);
+    }
+
+    #[test]
+    fn midi_learn_stays_off_without_the_embedded_server() {
+        let mut app = app_with_nodes(
+            vec![node(1, 0.0, 0.0, "param:2:20:200:exp")],
+            Point::new(1.0, 0.0),
+        );
+
+        app.handle_action(Action::MidiLearn);
+        assert_eq!(app.midi_learning, None);
+    }
+
     #[test]
     fn split_line_moves_right_side_and_lines_below_down() {
         let mut app = app_with_nodes(
@@ -1974,6 +2346,89 @@ mod tests {
         );
     }
 
+    #[test]
+    fn active_pattern_span_steps_through_polymetric_braces() {
+        assert_eq!(
+            active_pattern_span("{60,64,67}%4", false, 0.0, 0),
+            Some((1, 3))
+        );
+        assert_eq!(
+            active_pattern_span("{60,64,67}%4", false, 0.8, 0),
+            Some((1, 3))
+        );
+        assert_eq!(
+            active_pattern_span("{60,64,67}%4", false, 0.0, 1),
+            Some((4, 6))
+        );
+        assert_eq!(active_pattern_span("x{x..}%4", true, 0.9, 2), Some((4, 5)));
+    }
+
+    #[test]
+    fn active_pattern_span_wraps_to_the_period_end_before_a_delayed_first_cell() {
+        assert_eq!(
+            active_pattern_span("{x.}%2>0.5", true, 0.2, 0),
+            Some((2, 3))
+        );
+        // Period 3: the last alternative rings into cycle 0.
+        assert_eq!(
+            active_pattern_span("<60;64;67>>0.5", false, 0.25, 0),
+            Some((7, 9))
+        );
+        assert_eq!(
+            active_pattern_span("<60;64;67>>0.5", false, 0.25, 1),
+            Some((1, 3))
+        );
+    }
+
+    #[test]
+    fn active_pattern_span_covers_seq_step_records() {
+        assert_eq!(pattern_text("seq:x:0.8.x"), Some((false, true, "x:0.8.x")));
+        assert_eq!(
+            active_pattern_span("x:0.8.x:r3", true, 0.0, 0),
+            Some((0, 5))
+        );
+        assert_eq!(
+            active_pattern_span("x:0.8.x:r3", true, 0.5, 0),
+            Some((5, 6))
+        );
+        assert_eq!(
+            active_pattern_span("x:0.8.x:r3", true, 0.9, 0),
+            Some((6, 10))
+        );
+    }
+
+    #[test]
+    fn active_pattern_span_follows_swing_and_nudges() {
+        assert_eq!(
+            active_pattern_span("60,64:swing:1/2", false, 0.7, 0),
+            Some((0, 2))
+        );
+        assert_eq!(
+            active_pattern_span("60,64:swing:1/2", false, 0.8, 0),
+            Some((3, 5))
+        );
+        assert_eq!(active_pattern_span("x.x>0.5.", true, 0.6, 0), Some((1, 2)));
+        assert_eq!(active_pattern_span("x.x>0.5.", true, 0.7, 0), Some((2, 3)));
+        assert_eq!(
+            active_pattern_span("<60;64>>0.5", false, 0.25, 1),
+            Some((1, 3))
+        );
+        assert_eq!(
+            active_pattern_span("<60;64>>0.5", false, 0.75, 1),
+            Some((4, 6))
+        );
+    }
+
+    #[test]
+    fn active_pattern_span_skips_degrade_suffixes() {
+        assert_eq!(
+            active_pattern_span("60?0.3,64", false, 0.75, 0),
+            Some((7, 9))
+        );
+        assert_eq!(active_pattern_span("x?0.5.x?", true, 0.9, 0), Some((6, 7)));
+        assert_eq!(active_pattern_span("x?.x", true, 0.7, 0), Some((3, 4)));
+    }
+
     #[test]
     fn move_node_moves_node_and_cursor_when_target_has_room() {
         let mut app = app_with_nodes(
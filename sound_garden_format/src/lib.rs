@@ -8,6 +8,7 @@ use std::{collections::HashMap, convert::TryFrom};
 pub struct NodeRepository {
     nodes: Vec<Node>,
     cursor: Cursor,
+    midi_bindings: Vec<MidiBinding>,
     undo_stack: Vec<Snapshot>,
     redo_stack: Vec<Snapshot>,
     last_undo_group: Option<u64>,
@@ -23,6 +24,9 @@ struct Snapshot {
 struct StoredNodeRepository {
     nodes: Vec<StoredNode>,
     cursor: StoredPoint,
+    /// Missing in documents saved before MIDI learn.
+    #[serde(default)]
+    midi_bindings: Vec<StoredMidiBinding>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -32,6 +36,16 @@ struct StoredNode {
     text: String,
 }
 
+#[derive(Serialize, Deserialize)]
+struct StoredMidiBinding {
+    param: usize,
+    channel: u8,
+    controller: u8,
+    min: f64,
+    max: f64,
+    curve: MidiCurve,
+}
+
 #[derive(Clone, Copy, Default, Serialize, Deserialize)]
 struct StoredPoint {
     x: f64,
@@ -160,6 +174,16 @@ impl NodeRepository {
         self.cursor = cursor.clone();
     }
 
+    pub fn midi_bindings(&self) -> &[MidiBinding] {
+        &self.midi_bindings
+    }
+
+    /// Add a learned binding, replacing any binding for the same param or
+    /// the same control change. Not part of undo history.
+    pub fn set_midi_binding(&mut self, binding: MidiBinding) {
+        sound_garden_types::set_midi_binding(&mut self.midi_bindings, binding);
+    }
+
     fn snapshot(&self) -> Snapshot {
         Snapshot {
             nodes: self.nodes.clone(),
@@ -210,6 +234,11 @@ impl From<&NodeRepository> for StoredNodeRepository {
         Self {
             nodes: repo.nodes.iter().map(StoredNode::from).collect(),
             cursor: repo.cursor.position.into(),
+            midi_bindings: repo
+                .midi_bindings
+                .iter()
+                .map(StoredMidiBinding::from)
+                .collect(),
         }
     }
 }
@@ -221,6 +250,11 @@ impl From<StoredNodeRepository> for NodeRepository {
             cursor: Cursor {
                 position: repo.cursor.into(),
             },
+            midi_bindings: repo
+                .midi_bindings
+                .into_iter()
+                .map(MidiBinding::from)
+                .collect(),
             ..Self::new()
         };
         repo.sort_nodes();
@@ -248,6 +282,34 @@ impl From<StoredNode> for Node {
     }
 }
 
+impl From<&MidiBinding> for StoredMidiBinding {
+    fn from(binding: &MidiBinding) -> Self {
+        Self {
+            param: binding.mapping.param,
+            channel: binding.channel,
+            controller: binding.controller,
+            min: binding.mapping.min,
+            max: binding.mapping.max,
+            curve: binding.mapping.curve,
+        }
+    }
+}
+
+impl From<StoredMidiBinding> for MidiBinding {
+    fn from(binding: StoredMidiBinding) -> Self {
+        Self {
+            channel: binding.channel,
+            controller: binding.controller,
+            mapping: ParamMapping {
+                param: binding.param,
+                min: binding.min,
+                max: binding.max,
+                curve: binding.curve,
+            },
+        }
+    }
+}
+
 impl From<Point> for StoredPoint {
     fn from(point: Point) -> Self {
         Self {
@@ -375,6 +437,48 @@ mod tests {
         assert_eq!(nodes[0].id, Id::from(0x2));
     }
 
+    #[test]
+    fn midi_bindings_replace_by_param_or_control_and_survive_saving() {
+        let binding = |param, controller| MidiBinding {
+            channel: 0,
+            controller,
+            mapping: ParamMapping {
+                param,
+                min: 20.0,
+                max: 20000.0,
+                curve: MidiCurve::Exponential,
+            },
+        };
+        let mut repo = NodeRepository::new();
+        repo.set_midi_binding(binding(1, 74));
+        repo.set_midi_binding(binding(2, 71));
+        repo.set_midi_binding(binding(1, 71));
+        assert_eq!(repo.midi_bindings(), [binding(1, 71)]);
+
+        let mut bytes = Vec::new();
+        ciborium::into_writer(&repo, &mut bytes).unwrap();
+        let loaded = ciborium::from_reader::<NodeRepository, _>(bytes.as_slice()).unwrap();
+        assert_eq!(loaded.midi_bindings(), [binding(1, 71)]);
+    }
+
+    #[test]
+    fn documents_without_midi_bindings_still_load() {
+        #[derive(Serialize)]
+        struct OldRepository {
+            nodes: Vec<StoredNode>,
+            cursor: StoredPoint,
+        }
+        let mut bytes = Vec::new();
+        let old = OldRepository {
+            nodes: vec![StoredNode::from(&node(0x1, 0.0, 0.0, "sine"))],
+            cursor: StoredPoint::default(),
+        };
+        ciborium::into_writer(&old, &mut bytes).unwrap();
+        let loaded = ciborium::from_reader::<NodeRepository, _>(bytes.as_slice()).unwrap();
+        assert_eq!(loaded.nodes().len(), 1);
+        assert!(loaded.midi_bindings().is_empty());
+    }
+
     #[test]
     fn parses_text_format_and_rejects_invalid_ids() {
         let repo =
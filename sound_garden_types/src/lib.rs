@@ -1,3 +1,4 @@
+use rkyv::{Archive, Deserialize as RkyvDeserialize, Serialize as RkyvSerialize};
 use serde::{Deserialize, Serialize};
 use std::{
     convert::TryFrom,
@@ -138,6 +139,80 @@ pub struct Node {
     pub text: String,
 }
 
+/// How a learned control change maps onto its parameter's range.
+#[derive(
+    Archive,
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    RkyvSerialize,
+    RkyvDeserialize,
+    Serialize,
+    Deserialize,
+)]
+pub enum MidiCurve {
+    #[default]
+    Linear,
+    /// Equal ratios per step, e.g. for frequencies; linear unless both ends
+    /// are positive.
+    Exponential,
+}
+
+/// Where a learned control change lands: a `param:N` slot, its range and
+/// curve.
+#[derive(
+    Archive, Clone, Copy, Debug, PartialEq, RkyvSerialize, RkyvDeserialize, Serialize, Deserialize,
+)]
+pub struct ParamMapping {
+    pub param: usize,
+    pub min: f64,
+    pub max: f64,
+    pub curve: MidiCurve,
+}
+
+impl ParamMapping {
+    /// Parameter value for a normalized controller value `0..1`.
+    pub fn value(&self, x: f64) -> f64 {
+        let x = x.clamp(0.0, 1.0);
+        match self.curve {
+            MidiCurve::Exponential if self.min > 0.0 && self.max > 0.0 => {
+                self.min * (self.max / self.min).powf(x)
+            }
+            _ => self.min + (self.max - self.min) * x,
+        }
+    }
+}
+
+/// A MIDI control change bound to a `param:N` slot with MIDI learn. The
+/// audio server applies it and the document stores it.
+#[derive(
+    Archive, Clone, Copy, Debug, PartialEq, RkyvSerialize, RkyvDeserialize, Serialize, Deserialize,
+)]
+pub struct MidiBinding {
+    /// MIDI channel `0..15`.
+    pub channel: u8,
+    pub controller: u8,
+    pub mapping: ParamMapping,
+}
+
+impl MidiBinding {
+    /// One control change per param and one param per control change, so a
+    /// new binding replaces any sharing either.
+    pub fn replaces(&self, other: &MidiBinding) -> bool {
+        other.mapping.param == self.mapping.param
+            || (other.channel, other.controller) == (self.channel, self.controller)
+    }
+}
+
+/// Add `binding` to `bindings`, dropping the ones it replaces.
+pub fn set_midi_binding(bindings: &mut Vec<MidiBinding>, binding: MidiBinding) {
+    bindings.retain(|other| !binding.replaces(other));
+    bindings.push(binding);
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum MetaKey {
     Position(Id),